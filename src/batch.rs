@@ -0,0 +1,435 @@
+use crate::board::Board;
+use crate::budget::{Budget, StopReason};
+use crate::candidates;
+use crate::grade;
+use crate::search;
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+// Runs one job over every puzzle in a file with one puzzle per line, writing a row per puzzle as
+// it goes so collections of any size stream through in constant memory.
+//
+// A line holds the puzzle, either 81 characters or a 729-character candidate string, and then
+// any number of extra columns; those are carried through to the output untouched, one output
+// column each. The columns are split on whatever the first line uses: commas, with CSV quoting,
+// or tabs, or else runs of spaces. Quoted fields can't span lines. Blank lines and lines
+// starting with `#` or `//` are skipped, as is a header row at the top of a CSV.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Task {
+    Solve,
+    Grade,
+    Unique,
+}
+
+impl std::str::FromStr for Task {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Task, String> {
+        return match s {
+            "solve" => Ok(Task::Solve),
+            "grade" => Ok(Task::Grade),
+            "unique" => Ok(Task::Unique),
+            _ => Err(format!("unknown task: {}", s)),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Csv,
+    Ndjson,
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Output, String> {
+        return match s {
+            "csv" => Ok(Output::Csv),
+            "ndjson" => Ok(Output::Ndjson),
+            _ => Err(format!("unknown output format: {}", s)),
+        };
+    }
+}
+
+pub struct Entry {
+    // Line numbers start at 1.
+    pub line: usize,
+    pub puzzle: Board,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EntryError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Delimiter {
+    Comma,
+    Tab,
+    Spaces,
+}
+
+impl Delimiter {
+    fn detect(line: &str) -> Delimiter {
+        if line.contains(',') {
+            return Delimiter::Comma;
+        }
+        if line.contains('\t') {
+            return Delimiter::Tab;
+        }
+        return Delimiter::Spaces;
+    }
+    fn split(self, line: &str) -> Vec<String> {
+        return match self {
+            Delimiter::Comma => csv_fields(line),
+            Delimiter::Tab => line.split('\t').map(|field| field.to_string()).collect(),
+            Delimiter::Spaces => line
+                .split_whitespace()
+                .map(|field| field.to_string())
+                .collect(),
+        };
+    }
+}
+
+// One line of CSV. Quoted fields can hold commas, and `""` inside them is a quote.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::<String>::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(ch),
+        }
+    }
+    fields.push(field);
+    return fields;
+}
+
+fn parse_puzzle(field: &str) -> Result<Board, String> {
+    if field.len() == 729 {
+        return candidates::parse_line(field);
+    }
+    return field.parse();
+}
+
+pub struct Entries<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    seen_puzzle: bool,
+    // Set from the first line that isn't skipped.
+    delimiter: Option<Delimiter>,
+}
+
+pub fn entries<R: BufRead>(reader: R) -> Entries<R> {
+    return Entries {
+        lines: reader.lines(),
+        line: 0,
+        seen_puzzle: false,
+        delimiter: None,
+    };
+}
+
+impl<R: BufRead> Iterator for Entries<R> {
+    type Item = Result<Entry, EntryError>;
+    fn next(&mut self) -> Option<Result<Entry, EntryError>> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(err) => {
+                    self.line += 1;
+                    return Some(Err(EntryError {
+                        line: self.line,
+                        message: err.to_string(),
+                    }));
+                }
+            };
+            self.line += 1;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') || text.starts_with("//") {
+                continue;
+            }
+            let delimiter = *self.delimiter.get_or_insert(Delimiter::detect(text));
+            let mut fields = delimiter.split(text).into_iter();
+            let first = fields.next().unwrap_or_default();
+            let first = first.trim();
+            let puzzle = match parse_puzzle(first) {
+                Ok(puzzle) => puzzle,
+                // Column names, as at the top of the usual CSV datasets.
+                Err(_) if !self.seen_puzzle && first.chars().any(|ch| ch.is_alphabetic()) => {
+                    continue
+                }
+                Err(message) => {
+                    return Some(Err(EntryError {
+                        line: self.line,
+                        message,
+                    }))
+                }
+            };
+            self.seen_puzzle = true;
+            return Some(Ok(Entry {
+                line: self.line,
+                puzzle,
+                fields: fields.collect(),
+            }));
+        }
+    }
+}
+
+pub struct BatchOptions {
+    pub task: Task,
+    pub output: Output,
+    // Limits for each puzzle on its own, not the whole run.
+    pub timeout: Option<Duration>,
+    pub max_nodes: Option<u64>,
+}
+
+impl BatchOptions {
+    pub fn new(task: Task, output: Output) -> BatchOptions {
+        return BatchOptions {
+            task,
+            output,
+            timeout: None,
+            max_nodes: None,
+        };
+    }
+    fn budget(&self) -> Budget {
+        let mut budget = Budget::unlimited();
+        if let Some(timeout) = self.timeout {
+            budget = budget.with_timeout(timeout);
+        }
+        budget.max_nodes = self.max_nodes;
+        return budget;
+    }
+}
+
+// One output row. Which of the optional columns are filled depends on the task.
+#[derive(Default, Serialize)]
+pub struct Record {
+    pub line: usize,
+    pub puzzle: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solutions: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<grade::Difficulty>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hardest: Option<grade::Technique>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped: Option<StopReason>,
+    pub micros: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub fields: Vec<String>,
+}
+
+pub fn process(entry: &Entry, options: &BatchOptions) -> Record {
+    let started = Instant::now();
+    let mut record = Record {
+        line: entry.line,
+        puzzle: entry.puzzle.to_line(),
+        fields: entry.fields.clone(),
+        ..Record::default()
+    };
+    match options.task {
+        Task::Solve => {
+            let outcome = search::solutions_within(&entry.puzzle, 1, &options.budget());
+            record.solution = outcome.result.first().map(|solution| solution.to_line());
+            record.stopped = Some(outcome.stopped);
+            if outcome.is_complete() && record.solution.is_none() {
+                record.error = Some("no solution".to_string());
+            }
+        }
+        Task::Unique => {
+            let outcome = search::solutions_within(&entry.puzzle, 2, &options.budget());
+            record.solutions = Some(outcome.result.len());
+            record.stopped = Some(outcome.stopped);
+        }
+        Task::Grade => {
+            let graded = grade::grade(&entry.puzzle);
+            record.difficulty = Some(graded.difficulty);
+            record.hardest = Some(graded.hardest);
+        }
+    }
+    record.micros = started.elapsed().as_micros();
+    return record;
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_string();
+}
+
+// Enum values as they appear in the JSON output.
+fn name<T: Serialize>(value: &Option<T>) -> String {
+    return match value {
+        Some(value) => serde_json::to_value(value)
+            .ok()
+            .and_then(|value| value.as_str().map(|name| name.to_string()))
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+}
+
+fn csv_header(task: Task) -> &'static str {
+    return match task {
+        Task::Solve => "line,puzzle,solution,stopped,micros,error",
+        Task::Unique => "line,puzzle,solutions,stopped,micros,error",
+        Task::Grade => "line,puzzle,difficulty,hardest,micros,error",
+    };
+}
+
+fn csv_row(record: &Record, task: Task) -> String {
+    let result = match task {
+        Task::Solve => vec![
+            record.solution.clone().unwrap_or_default(),
+            name(&record.stopped),
+        ],
+        Task::Unique => vec![
+            record
+                .solutions
+                .map(|count| count.to_string())
+                .unwrap_or_default(),
+            name(&record.stopped),
+        ],
+        Task::Grade => vec![name(&record.difficulty), name(&record.hardest)],
+    };
+    let mut columns = vec![record.line.to_string(), record.puzzle.clone()];
+    columns.extend(result);
+    columns.push(record.micros.to_string());
+    columns.push(record.error.clone().unwrap_or_default());
+    // The line's own columns follow, as many as it had.
+    columns.extend(record.fields.iter().cloned());
+    return columns
+        .iter()
+        .map(|column| csv_field(column))
+        .collect::<Vec<String>>()
+        .join(",");
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub processed: usize,
+    // Lines that couldn't be read as a puzzle; they get a row with only the error filled in.
+    pub unreadable: usize,
+}
+
+pub fn run<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    options: &BatchOptions,
+) -> std::io::Result<Summary> {
+    let mut summary = Summary::default();
+    if options.output == Output::Csv {
+        writeln!(writer, "{}", csv_header(options.task))?;
+    }
+    for entry in entries(reader) {
+        let record = match entry {
+            Ok(entry) => {
+                summary.processed += 1;
+                process(&entry, options)
+            }
+            Err(err) => {
+                summary.unreadable += 1;
+                Record {
+                    line: err.line,
+                    error: Some(err.message),
+                    ..Record::default()
+                }
+            }
+        };
+        match options.output {
+            Output::Csv => writeln!(writer, "{}", csv_row(&record, options.task))?,
+            Output::Ndjson => writeln!(writer, "{}", serde_json::to_string(&record).unwrap())?,
+        }
+    }
+    return Ok(summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASY: &str =
+        "48.1..6.9..7.8.1.55.6.472....8..1...6.28735..7...6.9.3.746.8.9.86.7.2....3.....67";
+    const HARD: &str =
+        "..............12692...5...1....869...5..49..........7..38.7.6....5....97.9...5..4";
+    const INPUT: &str = "quizzes,difficulty,source\n\
+        # from the NYT\n\
+        48.1..6.9..7.8.1.55.6.472....8..1...6.28735..7...6.9.3.746.8.9.86.7.2....3.....67,easy,New York Times\n\
+        \n\
+        ..............12692...5...1....869...5..49..........7..38.7.6....5....97.9...5..4,\"hard, very\",\"the \"\"NYT\"\"\"\n\
+        not a puzzle\n\
+        .................................................................................\n";
+
+    #[test]
+    fn test_entries() {
+        let read: Vec<Result<Entry, EntryError>> = entries(INPUT.as_bytes()).collect();
+        assert_eq!(read.len(), 4);
+        let first = read[0].as_ref().ok().unwrap();
+        assert_eq!(
+            (first.line, first.fields.clone()),
+            (3, vec!["easy".to_string(), "New York Times".to_string()])
+        );
+        let second = read[1].as_ref().ok().unwrap();
+        assert_eq!(second.fields, vec!["hard, very", "the \"NYT\""]);
+        assert_eq!(read[2].as_ref().err().unwrap().line, 6);
+        assert!(read[3].is_ok());
+
+        // without commas on the first line, tabs split the columns, or else spaces do
+        let tabbed = format!("{}\thard nyt\tx\n{}\t\teasy\n", HARD, EASY);
+        let read: Vec<Entry> = entries(tabbed.as_bytes())
+            .map(|e| e.ok().unwrap())
+            .collect();
+        assert_eq!(read[0].fields, vec!["hard nyt", "x"]);
+        assert_eq!(read[1].fields, vec!["", "easy"]);
+        let spaced = format!("{}  hard nyt\n", HARD);
+        let read: Vec<Entry> = entries(spaced.as_bytes())
+            .map(|e| e.ok().unwrap())
+            .collect();
+        assert_eq!(read[0].fields, vec!["hard", "nyt"]);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut out = Vec::<u8>::new();
+        let options = BatchOptions::new(Task::Unique, Output::Csv);
+        let summary = run(INPUT.as_bytes(), &mut out, &options).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                processed: 3,
+                unreadable: 1
+            }
+        );
+        let csv = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], csv_header(Task::Unique));
+        assert!(rows[1].starts_with("3,48.1"));
+        assert!(rows[1].contains(",1,completed,"));
+        assert!(rows[1].ends_with(",,easy,New York Times"));
+        assert!(rows[2].ends_with(",,\"hard, very\",\"the \"\"NYT\"\"\""));
+        assert!(rows[4].contains(",2,completed,"));
+
+        let mut out = Vec::<u8>::new();
+        let options = BatchOptions::new(Task::Grade, Output::Ndjson);
+        run(INPUT.as_bytes(), &mut out, &options).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(String::from_utf8(out).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(first["difficulty"], "easy");
+        assert_eq!(first["fields"][0], "easy");
+    }
+}
@@ -0,0 +1,63 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{BufReader, BufWriter};
+use std::time::Duration;
+use sudokuthings::batch::{self, BatchOptions, Output};
+
+// Usage: `batch <solve|grade|unique> [--ndjson] [--timeout-ms N] [--nodes N] [FILE]`. Reads
+// standard input when no file is given, writes CSV (or NDJSON) to standard output, and reports
+// the totals on standard error.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (options, path) = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!(
+                "usage: batch <solve|grade|unique> [--ndjson] [--timeout-ms N] [--nodes N] [FILE]"
+            );
+            std::process::exit(2);
+        }
+    };
+    let stdout = std::io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    let summary = match path {
+        Some(path) => {
+            let file = std::fs::File::open(&path).unwrap_or_else(|err| {
+                eprintln!("couldn't open {}: {}", path, err);
+                std::process::exit(2);
+            });
+            batch::run(BufReader::new(file), &mut writer, &options)
+        }
+        None => batch::run(std::io::stdin().lock(), &mut writer, &options),
+    };
+    let summary = summary.unwrap();
+    eprintln!(
+        "{} puzzles processed, {} lines unreadable",
+        summary.processed, summary.unreadable
+    );
+}
+
+fn parse_args(args: &[String]) -> Result<(BatchOptions, Option<String>), String> {
+    let task = args.first().ok_or("missing task")?.parse()?;
+    let mut options = BatchOptions::new(task, Output::Csv);
+    let mut path = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || -> Result<u64, String> {
+            let value = rest.next().ok_or(format!("{} needs a value", arg))?;
+            return value
+                .parse()
+                .map_err(|_| format!("not a number: {}", value));
+        };
+        match arg.as_str() {
+            "--csv" => options.output = Output::Csv,
+            "--ndjson" => options.output = Output::Ndjson,
+            "--timeout-ms" => options.timeout = Some(Duration::from_millis(value()?)),
+            "--nodes" => options.max_nodes = Some(value()?),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    return Ok((options, path));
+}
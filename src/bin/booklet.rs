@@ -0,0 +1,142 @@
+#![allow(clippy::needless_return)]
+
+use sudokuthings::booklet::{self, BookletOptions, BookletPuzzle};
+use sudokuthings::catalog::{self, Filter};
+use sudokuthings::generate::{self, GenerateOptions};
+use sudokuthings::grade::Difficulty;
+
+const USAGE: &str = "usage: booklet [--out FILE] [--title TEXT] [--per-page N] [--page a4|letter] \
+                     [--no-answers] [--puzzle ID]... [--tag TAG] [--collection NAME] \
+                     [--generate DIFFICULTY:COUNT]... [--seed N]";
+
+// Writes a printable PDF of puzzles from the catalog, freshly generated ones, or both, with an
+// answer key at the back. Catalog puzzles come first, in the order asked for; `--tag` and
+// `--collection` add every entry that matches. Writes to standard output without `--out`.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let request = match parse_args(&args) {
+        Ok(request) => request,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let puzzles = match collect(&request) {
+        Ok(puzzles) => puzzles,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if puzzles.is_empty() {
+        eprintln!("no puzzles to print");
+        std::process::exit(1);
+    }
+    let pdf = booklet::booklet(&puzzles, &request.options);
+    match &request.out {
+        Some(path) => std::fs::write(path, pdf).unwrap_or_else(|err| {
+            eprintln!("couldn't write {}: {}", path, err);
+            std::process::exit(1);
+        }),
+        None => {
+            use std::io::Write;
+            std::io::stdout().write_all(&pdf).unwrap();
+        }
+    }
+    eprintln!("{} puzzles", puzzles.len());
+}
+
+struct Request {
+    options: BookletOptions,
+    out: Option<String>,
+    ids: Vec<String>,
+    filter: Option<Filter>,
+    generate: Vec<(Difficulty, usize)>,
+    seed: u64,
+}
+
+fn collect(request: &Request) -> Result<Vec<BookletPuzzle>, String> {
+    let mut puzzles = Vec::<BookletPuzzle>::new();
+    if !request.ids.is_empty() || request.filter.is_some() {
+        let catalog =
+            catalog::Catalog::load(&catalog::default_dir()).map_err(|err| err.to_string())?;
+        for id in &request.ids {
+            let entry = catalog.get(id).ok_or(format!("no puzzle with id {}", id))?;
+            puzzles.push(BookletPuzzle::from_entry(entry));
+        }
+        if let Some(filter) = &request.filter {
+            for entry in catalog.filter(filter) {
+                puzzles.push(BookletPuzzle::from_entry(entry));
+            }
+        }
+    }
+    let mut seed = request.seed;
+    for (difficulty, count) in &request.generate {
+        for _ in 0..*count {
+            let options = GenerateOptions::new(*difficulty).with_seed(seed);
+            seed += 1;
+            let generated = generate::generate(&options).map_err(|err| err.to_string())?;
+            let title = format!("Puzzle {}", options.seed);
+            puzzles.push(BookletPuzzle::from_generated(&generated, &title));
+        }
+    }
+    return Ok(puzzles);
+}
+
+fn parse_args(args: &[String]) -> Result<Request, String> {
+    let mut request = Request {
+        options: BookletOptions::new("Sudoku"),
+        out: None,
+        ids: vec![],
+        filter: None,
+        generate: vec![],
+        seed: 1,
+    };
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || -> Result<String, String> {
+            return rest.next().cloned().ok_or(format!("{} needs a value", arg));
+        };
+        match arg.as_str() {
+            "--no-answers" => request.options.answer_key = false,
+            "--out" => request.out = Some(value()?),
+            "--title" => request.options.title = value()?,
+            "--per-page" => {
+                let value = value()?;
+                request.options.per_page = value
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=9).contains(n))
+                    .ok_or(format!("puzzles per page must be 1 to 9: {}", value))?;
+            }
+            "--page" => request.options.page_size = value()?.parse()?,
+            "--puzzle" => request.ids.push(value()?),
+            "--tag" => request.filter.get_or_insert_with(Filter::default).tag = Some(value()?),
+            "--collection" => {
+                request
+                    .filter
+                    .get_or_insert_with(Filter::default)
+                    .collection = Some(value()?)
+            }
+            "--generate" => {
+                let value = value()?;
+                let (difficulty, count) = value
+                    .split_once(':')
+                    .ok_or(format!("expected DIFFICULTY:COUNT: {}", value))?;
+                let count = count
+                    .parse()
+                    .map_err(|_| format!("not a number: {}", count))?;
+                request.generate.push((difficulty.parse()?, count));
+            }
+            "--seed" => {
+                let value = value()?;
+                request.seed = value
+                    .parse()
+                    .map_err(|_| format!("not a number: {}", value))?;
+            }
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    return Ok(request);
+}
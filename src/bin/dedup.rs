@@ -0,0 +1,40 @@
+#![allow(clippy::needless_return)]
+
+use std::path::PathBuf;
+use sudokuthings::{canonical, catalog};
+
+// Reports puzzles in a catalog directory that are the same puzzle up to symmetry and
+// relabelling. Usage: `dedup [DIR]`, defaulting to the catalog the server reads. Exits with 1
+// when there are duplicates so it can guard a CI job.
+fn main() {
+    let dir = match std::env::args().nth(1) {
+        Some(dir) => PathBuf::from(dir),
+        None => catalog::default_dir(),
+    };
+    let catalog = match catalog::Catalog::load(&dir) {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    let duplicates = catalog.duplicates();
+    for group in &duplicates {
+        let hash = canonical::canonical_hash(&group[0].board());
+        let ids: Vec<String> = group
+            .iter()
+            .map(|entry| format!("{}/{}", entry.collection, entry.id))
+            .collect();
+        println!("{:016x} {}", hash, ids.join(" "));
+    }
+    if !duplicates.is_empty() {
+        eprintln!(
+            "{} duplicate puzzle(s) found",
+            duplicates
+                .iter()
+                .map(|group| group.len() - 1)
+                .sum::<usize>()
+        );
+        std::process::exit(1);
+    }
+}
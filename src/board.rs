@@ -1,8 +1,11 @@
+use crate::budget::{Budget, Meter, Outcome};
 use serde::ser::{SerializeStruct, SerializeTuple, Serializer};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::mem::{self, MaybeUninit};
+use std::str::FromStr;
 
 #[macro_export]
 macro_rules! set {
@@ -35,36 +38,109 @@ pub struct Board {
     pencilmarks: [HashSet<u8>; 81],
 }
 
+// How to treat the pencilmarks that came in with a board, since clients can send anything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PencilmarkPolicy {
+    // Use the marks as sent. An empty cell with no marks at all is read as untouched.
+    Trust,
+    // Throw the marks away and work them out again from the placed digits.
+    Recompute,
+    // Keep only the marks that are also consistent with the placed digits.
+    Intersect,
+}
+
+impl Default for PencilmarkPolicy {
+    fn default() -> PencilmarkPolicy {
+        return PencilmarkPolicy::Trust;
+    }
+}
+
+impl FromStr for PencilmarkPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PencilmarkPolicy, String> {
+        return match s {
+            "trust" => Ok(PencilmarkPolicy::Trust),
+            "recompute" => Ok(PencilmarkPolicy::Recompute),
+            "intersect" => Ok(PencilmarkPolicy::Intersect),
+            _ => Err(format!("unknown pencilmark policy: {}", s)),
+        };
+    }
+}
+
+// The order in which the naturalistic solver visits boxes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoxSchedule {
+    // Walk the boxes in order, starting after the last one visited.
+    RoundRobin,
+    // The box with the most digits already placed.
+    MostFilled,
+    // The box whose surroundings changed most recently.
+    MostRecentChange,
+    // Every box that has something to do, in one pass over the whole board.
+    Global,
+}
+
+impl Default for BoxSchedule {
+    fn default() -> BoxSchedule {
+        return BoxSchedule::RoundRobin;
+    }
+}
+
+impl FromStr for BoxSchedule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<BoxSchedule, String> {
+        return match s {
+            "round_robin" => Ok(BoxSchedule::RoundRobin),
+            "most_filled" => Ok(BoxSchedule::MostFilled),
+            "most_recent" => Ok(BoxSchedule::MostRecentChange),
+            "global" => Ok(BoxSchedule::Global),
+            _ => Err(format!("unknown box schedule: {}", s)),
+        };
+    }
+}
+
+// Something on the board that its peers haven't reacted to yet.
+#[derive(Clone, Copy)]
+enum Change {
+    Placed(usize),
+    Eliminated(usize),
+}
+
 #[derive(Clone)]
 pub struct Solver {
     board: Board,
     boxes: [bool; 9],
     last_box: u8,
+    schedule: BoxSchedule,
+    // A box is dirty if something that could affect it changed since it was last worked. Clean
+    // boxes are skipped, so the solver stops instead of spinning once it runs out of moves.
+    dirty: [bool; 9],
+    dirtied_at: [u32; 9],
+    clock: u32,
+    queue: VecDeque<Change>,
 }
 
 impl Solver {
     fn mark_if_finished(&mut self, bx: SudokuBox) {
-        println!(
-            "Lef to place in box {}: {}",
-            bx.idx,
-            self.board.left_to_place(&bx).len()
-        );
-        if self.board.left_to_place(&bx).len() == 0 {
+        if self.board.left_to_place(&bx).is_empty() {
             self.boxes[bx.idx] = true;
         }
     }
+    fn needs_work(&self, idx: usize) -> bool {
+        return !self.boxes[idx] && self.dirty[idx];
+    }
     fn get_unsolved_box(&mut self) -> Option<SudokuBox> {
-        let mut all_solved = true;
+        let mut any_left = false;
         for i in 0..9 {
-            all_solved = all_solved & self.boxes[i as usize];
+            any_left |= self.needs_work(i);
         }
-        if !all_solved {
+        if any_left {
             let mut idx = self.last_box;
             idx += 1;
             if idx > 8 {
                 idx = 0;
             }
-            while self.boxes[idx as usize] {
+            while !self.needs_work(idx as usize) {
                 if idx < 8 {
                     idx += 1;
                 } else {
@@ -72,25 +148,111 @@ impl Solver {
                 }
             }
             self.last_box = idx;
-            return Some(self.board.get_box(idx as u8));
+            return Some(self.board.get_box(idx));
         };
         return None;
     }
-    pub fn init_with_board(mut board: Board) -> Solver {
-        // if we're just initializing the board assume that any empty squares without pencilmarks are
-        // actually untouched, not that they represent a contradiction.
-        for i in 0..81 {
-            if let None = board.digits[i] {
-                if board.pencilmarks[i].len() == 0 {
-                    board.pencilmarks[i] = one_to_nine();
+    // Picks the best box by `score`, breaking ties in round-robin order.
+    fn get_best_box<F>(&mut self, score: F) -> Option<SudokuBox>
+    where
+        F: Fn(&Solver, usize) -> u32,
+    {
+        let mut best: Option<(u8, u32)> = None;
+        for offset in 1..10 {
+            let idx = (self.last_box + offset) % 9;
+            if !self.needs_work(idx as usize) {
+                continue;
+            }
+            let current = score(self, idx as usize);
+            match best {
+                Some((_, best_score)) if best_score >= current => {}
+                _ => best = Some((idx, current)),
+            }
+        }
+        let (idx, _) = best?;
+        self.last_box = idx;
+        return Some(self.board.get_box(idx));
+    }
+    // The boxes to work next under the current schedule. Empty once the board is solved or there
+    // is nothing left that could make progress.
+    fn next_boxes(&mut self) -> Vec<SudokuBox> {
+        let next = match self.schedule {
+            BoxSchedule::RoundRobin => self.get_unsolved_box(),
+            BoxSchedule::MostFilled => self.get_best_box(|solver, idx| {
+                let bx = solver.board.get_box(idx as u8);
+                return solver.board.placed(&bx).len() as u32;
+            }),
+            BoxSchedule::MostRecentChange => {
+                self.get_best_box(|solver, idx| solver.dirtied_at[idx])
+            }
+            BoxSchedule::Global => {
+                return (0..9u8)
+                    .filter(|idx| self.needs_work(*idx as usize))
+                    .map(|idx| self.board.get_box(idx))
+                    .collect();
+            }
+        };
+        return next.into_iter().collect();
+    }
+    fn dirty_box(&mut self, idx: usize) {
+        self.dirty[idx] = true;
+        self.dirtied_at[idx] = self.clock;
+    }
+    fn place(&mut self, idx: usize, digit: u8) {
+        self.board.place_mut(idx, digit);
+        self.queue.push_back(Change::Placed(idx));
+    }
+    fn eliminate(&mut self, idx: usize, digits: &HashSet<u8>) {
+        if self.board.digits[idx].is_some() {
+            return;
+        }
+        let before = self.board.pencilmarks[idx].len();
+        self.board.pencil_out_mut(idx, digits);
+        if self.board.pencilmarks[idx].len() != before {
+            self.queue.push_back(Change::Eliminated(idx));
+        }
+    }
+    // Work through the queue of changes. A placement pencils its digit out of its peers and
+    // nothing else, and any cell whose pencilmarks changed dirties its box for the scheduler.
+    fn propagate(&mut self) {
+        while let Some(change) = self.queue.pop_front() {
+            self.clock += 1;
+            match change {
+                Change::Placed(idx) => {
+                    if let Some(digit) = self.board.digits[idx] {
+                        let digits = set!(digit);
+                        for peer in peers(idx) {
+                            self.eliminate(peer, &digits);
+                        }
+                    }
+                    self.dirty_box(get_box_index(idx) as usize);
                 }
+                Change::Eliminated(idx) => self.dirty_box(get_box_index(idx) as usize),
             }
         }
+    }
+    pub fn with_schedule(mut self, schedule: BoxSchedule) -> Solver {
+        self.schedule = schedule;
+        return self;
+    }
+    pub fn init_with_board(mut board: Board, policy: PencilmarkPolicy) -> Solver {
+        board.apply_pencilmark_policy(policy);
         let mut new_solver = Solver {
             board,
             boxes: [false; 9],
             last_box: 0,
+            schedule: BoxSchedule::default(),
+            dirty: [true; 9],
+            dirtied_at: [0; 9],
+            clock: 0,
+            queue: VecDeque::new(),
         };
+        // every digit already on the board still has to be penciled out of its peers
+        for i in 0..81 {
+            if new_solver.board.digits[i].is_some() {
+                new_solver.queue.push_back(Change::Placed(i));
+            }
+        }
         for j in 0..9 {
             new_solver.mark_if_finished(new_solver.board.get_box(j));
         }
@@ -105,6 +267,18 @@ fn one_to_nine() -> HashSet<u8> {
     set!(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8)
 }
 
+// Every other cell sharing a row, column or box with `idx`.
+fn peers(idx: usize) -> Vec<usize> {
+    let bx = get_box_index(idx);
+    let mut peers = Vec::<usize>::with_capacity(20);
+    for i in 0..81 {
+        if i != idx && (i / 9 == idx / 9 || i % 9 == idx % 9 || get_box_index(i) == bx) {
+            peers.push(i);
+        }
+    }
+    return peers;
+}
+
 fn get_box_index(idx: usize) -> u8 {
     let row = idx % 9;
     let col = idx / 9;
@@ -135,37 +309,72 @@ impl Board {
         let left_to_place = iter_to_set(one_to_nine().difference(&self.placed(bx)));
         return left_to_place;
     }
-    fn pencil_out(&self, idx: usize, pencilmarks: &HashSet<u8>) -> Board {
-        let mut new_marks = self.pencilmarks[idx].clone();
-        for i in pencilmarks {
-            new_marks.remove(&i);
-        }
-        let mut new_board_marks = self.pencilmarks.clone();
-        new_board_marks[idx] = new_marks;
-        return Board {
-            digits: self.digits,
-            pencilmarks: new_board_marks,
-        };
-    }
     fn pencil_out_mut(&mut self, idx: usize, pencilmarks: &HashSet<u8>) {
         for i in pencilmarks {
-            self.pencilmarks[idx].remove(&i);
+            self.pencilmarks[idx].remove(i);
         }
     }
-    fn place(&self, idx: usize, digit: u8) -> Board {
-        let mut digits = self.digits;
-        digits[idx] = Some(digit);
-        let mut pencilmarks = self.pencilmarks.clone();
-        pencilmarks[idx] = HashSet::<u8>::new();
-        return Board {
-            digits,
-            pencilmarks,
-        };
-    }
     fn place_mut(&mut self, idx: usize, digit: u8) {
         self.digits[idx] = Some(digit);
         self.pencilmarks[idx] = HashSet::<u8>::new();
     }
+    pub fn digit(&self, idx: usize) -> Option<u8> {
+        return self.digits[idx];
+    }
+    pub fn pencilmarks(&self, idx: usize) -> &HashSet<u8> {
+        return &self.pencilmarks[idx];
+    }
+    // Every digit placed in the same row, column or box as `idx`, not counting `idx` itself.
+    fn seen_digits(&self, idx: usize) -> HashSet<u8> {
+        let mut seen = HashSet::<u8>::new();
+        for j in peers(idx) {
+            if let Some(digit) = self.digits[j] {
+                seen.insert(digit);
+            }
+        }
+        return seen;
+    }
+    pub fn apply_pencilmark_policy(&mut self, policy: PencilmarkPolicy) {
+        for i in 0..81 {
+            if self.digits[i].is_some() {
+                continue;
+            }
+            // if we're just initializing the board assume that any empty squares without
+            // pencilmarks are actually untouched, not that they represent a contradiction.
+            let untouched = self.pencilmarks[i].is_empty();
+            match policy {
+                PencilmarkPolicy::Trust => {
+                    if untouched {
+                        self.pencilmarks[i] = one_to_nine();
+                    }
+                }
+                PencilmarkPolicy::Recompute => {
+                    self.pencilmarks[i] = one_to_nine();
+                    let seen = self.seen_digits(i);
+                    self.pencil_out_mut(i, &seen);
+                }
+                PencilmarkPolicy::Intersect => {
+                    if untouched {
+                        self.pencilmarks[i] = one_to_nine();
+                    }
+                    let seen = self.seen_digits(i);
+                    self.pencil_out_mut(i, &seen);
+                }
+            }
+        }
+    }
+    pub fn set_pencilmarks(&mut self, idx: usize, pencilmarks: HashSet<u8>) {
+        self.pencilmarks[idx] = pencilmarks;
+    }
+    pub fn from_digits(digits: [Option<u8>; 81]) -> Board {
+        let mut board = Board::empty_board();
+        for (idx, digit) in digits.iter().enumerate() {
+            if let Some(digit) = digit {
+                board.place_mut(idx, *digit);
+            }
+        }
+        return board;
+    }
     pub fn new(digits: HashMap<usize, u8>) -> Board {
         let mut board = Board::empty_board();
         for (key, val) in digits {
@@ -173,33 +382,56 @@ impl Board {
         }
         return board;
     }
-    fn from_square_state_vec(squares: Vec<SquareState>) -> Board {
+    pub fn from_square_state_vec(squares: Vec<SquareState>) -> Board {
         let mut board = Board::empty_board();
-        for i in 0..squares.len() {
-            match squares[i].contents {
+        for (i, square) in squares.iter().enumerate() {
+            match square.contents {
                 None => {}
                 Some(j) => {
                     board.place_mut(i, j);
                     continue;
                 }
             }
-            board.pencilmarks[i] = squares[i].pencilmarks.clone();
+            board.pencilmarks[i] = square.pencilmarks.clone();
         }
         return board;
     }
-    pub fn from_str(str: String) -> Board {
-        let square_state_vec: Vec<SquareState> = serde_json::from_str(&str).unwrap();
-        return Board::from_square_state_vec(square_state_vec);
+    // For squares from a client: the count and the digits are checked rather than trusted.
+    pub fn try_from_square_state_vec(squares: Vec<SquareState>) -> Result<Board, String> {
+        if squares.len() != 81 {
+            return Err(format!("board has {} squares, expected 81", squares.len()));
+        }
+        for (idx, square) in squares.iter().enumerate() {
+            let mut digits = square.contents.iter().chain(square.pencilmarks.iter());
+            if let Some(digit) = digits.find(|digit| !(1..=9).contains(*digit)) {
+                return Err(format!("not a digit in square {}: {}", idx, digit));
+            }
+        }
+        return Ok(Board::from_square_state_vec(squares));
+    }
+    // The one-line format: 81 characters read across then down, `.` for a blank. Pencilmarks
+    // are dropped.
+    pub fn to_line(&self) -> String {
+        return self
+            .digits
+            .iter()
+            .map(|digit| match digit {
+                Some(digit) => (b'0' + digit) as char,
+                None => '.',
+            })
+            .collect();
     }
 
     fn empty_board() -> Board {
         // It's a good thing this heads off a fuckton of bugs, because it's a royal pain in the ass.
         let mut pencilmarks: [MaybeUninit<HashSet<u8>>; 81] =
             unsafe { MaybeUninit::uninit().assume_init() };
-        for i in 0..81 {
-            pencilmarks[i] = MaybeUninit::new(one_to_nine());
+        for mark in &mut pencilmarks {
+            *mark = MaybeUninit::new(one_to_nine());
         }
-        let pencilmarks = unsafe { mem::transmute::<_, [HashSet<u8>; 81]>(pencilmarks) };
+        let pencilmarks = unsafe {
+            mem::transmute::<[MaybeUninit<HashSet<u8>>; 81], [HashSet<u8>; 81]>(pencilmarks)
+        };
         return Board {
             digits: [None; 81],
             pencilmarks,
@@ -224,6 +456,31 @@ impl Serialize for Board {
     }
 }
 
+// Reads the one-line format. Blanks may be written `.` or `0`, and surrounding whitespace is
+// ignored so lines can come straight out of a file.
+impl FromStr for Board {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Board, String> {
+        let mut digits = [None; 81];
+        let mut count = 0;
+        for ch in s.trim().chars() {
+            let digit = match ch {
+                '1'..='9' => Some(ch as u8 - b'0'),
+                '.' | '0' => None,
+                _ => return Err(format!("unexpected character in puzzle: {}", ch)),
+            };
+            if count < 81 {
+                digits[count] = digit;
+            }
+            count += 1;
+        }
+        if count != 81 {
+            return Err(format!("puzzle has {} cells, expected 81", count));
+        }
+        return Ok(Board::from_digits(digits));
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SquareState {
     contents: Option<u8>,
@@ -243,8 +500,6 @@ impl Serialize for SquareState {
 }
 
 struct SudokuBox {
-    rows: HashSet<u8>,
-    cols: HashSet<u8>,
     inds: HashSet<u8>,
     idx: usize,
 }
@@ -256,283 +511,47 @@ impl SudokuBox {
                 inds.insert(9 * i + j);
             }
         }
-        return SudokuBox {
-            rows,
-            cols,
-            inds,
-            idx,
-        };
-    }
-    fn row_inds_outside_box(&self, row: u8) -> HashSet<u8> {
-        let row_inds = set!(
-            9 * row,
-            1 + 9 * row,
-            2 + 9 * row,
-            3 + 9 * row,
-            4 + 9 * row,
-            5 + 9 * row,
-            6 + 9 * row,
-            7 + 9 * row,
-            8 + 9 * row
-        );
-        let diff = iter_to_set(row_inds.difference(&self.inds));
-        return diff;
-    }
-    fn row_inds_inside_box(&self, row: u8) -> HashSet<u8> {
-        let row_inds = set!(
-            9 * row,
-            1 + 9 * row,
-            2 + 9 * row,
-            3 + 9 * row,
-            4 + 9 * row,
-            5 + 9 * row,
-            6 + 9 * row,
-            7 + 9 * row,
-            8 + 9 * row
-        );
-        let intersect = iter_to_set(row_inds.intersection(&self.inds));
-        return intersect;
-    }
-    fn col_inds_outside_box(&self, col: u8) -> HashSet<u8> {
-        let col_inds = set!(
-            col,
-            col + 9,
-            col + 18,
-            col + 27,
-            col + 36,
-            col + 45,
-            col + 54,
-            col + 63,
-            col + 72
-        );
-        let diff = iter_to_set(col_inds.difference(&self.inds));
-        return diff;
-    }
-    fn col_inds_inside_box(&self, col: u8) -> HashSet<u8> {
-        let col_inds = set!(
-            col,
-            col + 9,
-            col + 18,
-            col + 27,
-            col + 36,
-            col + 45,
-            col + 54,
-            col + 63,
-            col + 72
-        );
-        let intersect = iter_to_set(col_inds.intersection(&self.inds));
-        return intersect;
+        return SudokuBox { inds, idx };
     }
 }
 
 //Conventional digit placement.
-fn type_1_pencilmark_collapse(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
-    for i in board.left_to_place(current_box) {
+fn type_1_pencilmark_collapse(solver: &mut Solver, current_box: &SudokuBox) {
+    for i in solver.board.left_to_place(current_box) {
         let mut count = 0;
         let mut last_index: Option<usize> = None;
-        for j in current_box.inds.clone() {
-            if board.pencilmarks[j as usize].contains(&i) {
+        for j in current_box.inds.iter() {
+            if solver.board.pencilmarks[*j as usize].contains(&i) {
                 count += 1;
-                last_index.replace(j as usize);
+                last_index.replace(*j as usize);
             }
         }
         if count == 1 {
             if let Some(j) = last_index {
-                next_board.place_mut(j as usize, i);
-                next_board.pencilmarks[j as usize] = HashSet::<u8>::new();
+                solver.place(j, i);
             }
         }
     }
-
-    return next_board;
 }
 
 //Determine 'naked singles'
-fn type_2_pencilmark_collapse(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
-
-    for i in current_box.inds.clone() {
-        if board.pencilmarks[i as usize].len() == 1 {
-            let digits = board.pencilmarks[i as usize].clone();
-            println!("Digits: {:?}", digits);
+fn type_2_pencilmark_collapse(solver: &mut Solver, current_box: &SudokuBox) {
+    for i in current_box.inds.iter() {
+        let i = *i as usize;
+        if solver.board.digits[i].is_none() && solver.board.pencilmarks[i].len() == 1 {
+            let digits = solver.board.pencilmarks[i].clone();
             for digit in digits {
-                println!("Digit:{}", digit);
-                next_board.place_mut(i as usize, digit);
-            }
-            next_board.pencilmarks[i as usize] = HashSet::<u8>::new();
-        }
-    }
-
-    // for i in board.left_to_place(current_box) {
-    //     let mut count = 0;
-    //     let mut last_index: Option<usize> = None;
-    //     for j in current_box.inds.clone() {
-    //         if board.pencilmarks[j as usize].contains(&i) {
-    //             count += 1;
-    //             last_index.replace(j as usize);
-    //         }
-    //     }
-    //     if count == 1 {
-    //         if let Some(j) = last_index {
-    //             next_board.place_mut(j as usize, i);
-    //             next_board.pencilmarks[j as usize] = HashSet::<u8>::new();
-    //         }
-    //     }
-    // }
-
-    return next_board;
-}
-
-fn place_simple_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
-
-    //rows first
-    for i in current_box.rows.clone() {
-        let mut digits_seen = HashSet::<u8>::new();
-        for j in current_box.row_inds_outside_box(i) {
-            if let Some(l) = board.digits[j as usize] {
-                digits_seen.insert(l);
-                // println!("Seen digit {} in row {}", l, i);
-            }
-        }
-        for k in current_box.row_inds_inside_box(i) {
-            // println!("Penciling out options from row {}, box {}", i, k);
-            next_board.pencil_out_mut(k as usize, &board.placed(current_box));
-            next_board.pencil_out_mut(k as usize, &digits_seen);
-        }
-    }
-
-    //then cols
-    for i in current_box.cols.clone() {
-        let mut digits_seen = HashSet::<u8>::new();
-        for j in current_box.col_inds_outside_box(i) {
-            if let Some(l) = board.digits[j as usize] {
-                digits_seen.insert(l);
-                // println!("Seen digit {} in column {}", l, i);
-            }
-        }
-        for k in current_box.col_inds_inside_box(i) {
-            // println!("Penciling out options from column {}, box {}", i, k);
-            next_board.pencil_out_mut(k as usize, &board.placed(current_box));
-            next_board.pencil_out_mut(k as usize, &digits_seen);
-        }
-    }
-    return next_board;
-}
-
-// If a digit is only possible in a certain row or column outside of the current box
-// it can be penciled out of that row or column in the current box.
-
-fn place_derived_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
-
-    //rows first
-    for i in current_box.rows.clone() {
-        let mut other_rows = current_box.rows.clone();
-        other_rows.remove(&i);
-        let mut box_a_pencilmarks_in_row = HashSet::<u8>::new();
-        let mut box_a_pencilmarks_in_other_rows = HashSet::<u8>::new();
-        let mut box_b_pencilmarks_in_row = HashSet::<u8>::new();
-        let mut box_b_pencilmarks_in_other_rows = HashSet::<u8>::new();
-        let mut box_a_idx: u8 = 10;
-        for j in current_box.row_inds_outside_box(i) {
-            if box_a_idx == 10 {
-                box_a_idx = get_box_index(j as usize);
-            }
-            if box_a_idx == get_box_index(j as usize) {
-                box_a_pencilmarks_in_row =
-                    iter_to_set(box_a_pencilmarks_in_row.union(&board.pencilmarks[j as usize]));
-            } else {
-                box_b_pencilmarks_in_row =
-                    iter_to_set(box_b_pencilmarks_in_row.union(&board.pencilmarks[j as usize]));
-            }
-        }
-        for h in other_rows {
-            for j in current_box.row_inds_outside_box(h) {
-                if box_a_idx == get_box_index(j as usize) {
-                    box_a_pencilmarks_in_other_rows = iter_to_set(
-                        box_a_pencilmarks_in_other_rows.union(&board.pencilmarks[j as usize]),
-                    );
-                } else {
-                    box_b_pencilmarks_in_other_rows = iter_to_set(
-                        box_b_pencilmarks_in_other_rows.union(&board.pencilmarks[j as usize]),
-                    );
-                }
-            }
-        }
-
-        let box_a_elidable_pencilmarks =
-            iter_to_set(box_a_pencilmarks_in_row.difference(&box_a_pencilmarks_in_other_rows));
-        let box_b_elidable_pencilmarks =
-            iter_to_set(box_b_pencilmarks_in_row.difference(&box_b_pencilmarks_in_other_rows));
-        let elidable_pencilmarks =
-            iter_to_set(box_a_elidable_pencilmarks.union(&box_b_elidable_pencilmarks));
-        for k in current_box.row_inds_inside_box(i) {
-            // println!("Penciling out options from row {}, box {}", i, k);
-            next_board.pencil_out_mut(k as usize, &elidable_pencilmarks);
-        }
-    }
-
-    //then cols
-    for i in current_box.cols.clone() {
-        let mut other_cols = current_box.cols.clone();
-        other_cols.remove(&i);
-        let mut pencilmarks_in_col = HashSet::<u8>::new();
-        let mut pencilmarks_in_other_cols = HashSet::<u8>::new();
-        for j in current_box.col_inds_outside_box(i) {
-            pencilmarks_in_col =
-                iter_to_set(pencilmarks_in_col.union(&board.pencilmarks[j as usize]));
-        }
-        for h in other_cols {
-            for j in current_box.col_inds_outside_box(h) {
-                pencilmarks_in_other_cols =
-                    iter_to_set(pencilmarks_in_other_cols.union(&board.pencilmarks[j as usize]));
-            }
-        }
-        let elidable_pencilmarks =
-            iter_to_set(pencilmarks_in_col.difference(&pencilmarks_in_other_cols));
-        for k in current_box.row_inds_inside_box(i) {
-            next_board.pencil_out_mut(k as usize, &elidable_pencilmarks);
-        }
-    }
-
-    return next_board;
-}
-
-// If you have n cells whose pencilmarks are all a subset of a subset of size n,
-// You can eliminate those pencilmarks from all other cells.
-
-// For naturalism reasons, this will only look at sets already occuring in the box.
-fn resolve_subset_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
-
-    for i in current_box.inds.clone() {
-        let current_marks = board.pencilmarks[i as usize].clone();
-        let mut similar_boxes = HashSet::<u8>::new();
-        //find the squares whose pencilmarks are a subset of this one
-        for j in current_box.inds.clone() {
-            if let Some(_) = board.digits[j as usize] {
-                continue;
-            }
-            if board.pencilmarks[j as usize].is_subset(&current_marks) {
-                similar_boxes.insert(j);
-            }
-        }
-        //If the number of squares matches the size of the subset, those
-        // numbers are all constrained to these squares so pencil them out!
-        if similar_boxes.len() == current_marks.len() {
-            for j in iter_to_set(current_box.inds.clone().difference(&similar_boxes)) {
-                next_board.pencil_out_mut(j as usize, &current_marks);
+                solver.place(i, digit);
             }
+            // Pencil it out of the rest of the box before looking at the next cell.
+            solver.propagate();
         }
     }
-
-    return next_board;
 }
 
-fn resolve_cycle_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
-    let mut next_board = board.clone();
+fn resolve_cycle_pencilmarks(solver: &mut Solver, current_box: &SudokuBox) {
+    let board = &solver.board;
+    let mut eliminations = Vec::<(usize, HashSet<u8>)>::new();
     let mut already_visited = HashSet::<u8>::new();
     for i in current_box.inds.clone() {
         if already_visited.contains(&i) {
@@ -549,7 +568,7 @@ fn resolve_cycle_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
         let mut digits_seen = cycle_start.clone();
         let mut closed: bool = false;
         let mut finished: bool = false;
-        while finished == false {
+        while !finished {
             finished = true;
             for j in current_box.inds.clone() {
                 if already_visited.contains(&j) {
@@ -581,20 +600,28 @@ fn resolve_cycle_pencilmarks(board: Board, current_box: &SudokuBox) -> Board {
         already_visited = iter_to_set(already_visited.union(&cycle_boxes));
         if closed {
             for j in iter_to_set(current_box.inds.clone().difference(&cycle_boxes)) {
-                next_board.pencil_out_mut(j as usize, &digits_seen);
+                eliminations.push((j as usize, digits_seen.clone()));
             }
         }
     }
 
-    return next_board;
+    for (j, digits) in eliminations {
+        solver.eliminate(j, &digits);
+    }
 }
 
+// Neither of these clone the solver or revisit the whole box's pencilmarks: anything placed
+// elsewhere since the last visit has already been penciled out of this box by `propagate`.
 fn pencil_and_place_simple(board: Solver, current_box: SudokuBox) -> Solver {
-    let mut next_board = board.clone();
+    let mut next_board = board;
+
+    next_board.dirty[current_box.idx] = false;
+
+    next_board.propagate();
 
-    next_board.board = place_simple_pencilmarks(next_board.board, &current_box);
+    type_1_pencilmark_collapse(&mut next_board, &current_box);
 
-    next_board.board = type_1_pencilmark_collapse(next_board.board, &current_box);
+    next_board.propagate();
 
     next_board.mark_if_finished(current_box);
 
@@ -602,58 +629,170 @@ fn pencil_and_place_simple(board: Solver, current_box: SudokuBox) -> Solver {
 }
 
 fn pencil_and_place_complex(board: Solver, current_box: SudokuBox) -> Solver {
-    let mut next_board = board.clone();
+    let mut next_board = board;
 
-    next_board.board = place_simple_pencilmarks(next_board.board, &current_box);
+    next_board.dirty[current_box.idx] = false;
+
+    next_board.propagate();
 
     // next_board.board = place_derived_pencilmarks(next_board.board, &current_box);
 
-    next_board.board = resolve_cycle_pencilmarks(next_board.board, &current_box);
+    resolve_cycle_pencilmarks(&mut next_board, &current_box);
 
     // next_board.board = resolve_subset_pencilmarks(next_board.board, &current_box);
 
-    next_board.board = type_1_pencilmark_collapse(next_board.board, &current_box);
+    type_1_pencilmark_collapse(&mut next_board, &current_box);
+
+    next_board.propagate();
+
+    type_2_pencilmark_collapse(&mut next_board, &current_box);
 
-    next_board.board = type_2_pencilmark_collapse(next_board.board, &current_box);
+    next_board.propagate();
 
     next_board.mark_if_finished(current_box);
 
     return next_board;
 }
 
+// Work the given box, or if none is given whatever the solver's schedule picks next. Under the
+// global schedule that is a whole pass over the board.
 pub fn work_one_box(solver: Solver, box_index: Option<u8>) -> Solver {
     let mut new_solver = solver;
-    let working_box = match box_index {
-        None => match new_solver.get_unsolved_box() {
-            Some(sudoku_box) => sudoku_box,
-            None => return new_solver,
-        },
-        Some(box_index) => new_solver.board.get_box(box_index),
+    let working_boxes = match box_index {
+        None => new_solver.next_boxes(),
+        Some(box_index) => vec![new_solver.board.get_box(box_index)],
     };
-    return pencil_and_place_complex(new_solver, working_box);
+    for working_box in working_boxes {
+        new_solver = pencil_and_place_complex(new_solver, working_box);
+    }
+    return new_solver;
+}
+
+pub fn solve(board: Board, policy: PencilmarkPolicy) -> Board {
+    return solve_with_solver(Solver::init_with_board(board, policy));
+}
+
+pub fn solve_with_solver(solver: Solver) -> Board {
+    return solve_within(solver, &Budget::unlimited()).result;
 }
 
-pub fn solve(board: Board) -> Board {
+// Run the solver until it finishes or the budget runs out, each box worked costing one step. A
+// partial result is the board as far as the solver got.
+pub fn solve_within(solver: Solver, budget: &Budget) -> Outcome<Board> {
+    let mut meter = Meter::new(budget);
     let mut unsolved = true;
-    let mut solver = Solver::init_with_board(board.clone());
+    let mut solver = solver;
     while unsolved {
-        // get an unsolved box
-        let current_box = match solver.get_unsolved_box() {
-            Some(bx) => bx,
-            None => {
-                unsolved = false;
-                continue;
+        // get the unsolved boxes to work next
+        let current_boxes = solver.next_boxes();
+        if current_boxes.is_empty() {
+            unsolved = false;
+            continue;
+        }
+        for current_box in current_boxes {
+            if let Some(reason) = meter.step() {
+                return meter.finish(solver.board, Some(reason));
             }
-        };
-        solver = pencil_and_place_simple(solver, current_box);
+            solver = pencil_and_place_simple(solver, current_box);
+        }
     }
-    return solver.board;
+    return meter.finish(solver.board, None);
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_things() {}
     #[test]
     fn test_idx() {}
+    #[test]
+    fn test_pencilmark_policies() {
+        // 4 is placed at index 0 in the easy board, so it can't be a candidate in its row.
+        let mut board = crate::catalog::test_board("nyt-easy");
+        board.set_pencilmarks(2, set!(4u8));
+        board.set_pencilmarks(4, HashSet::<u8>::new());
+
+        let mut trusted = board.clone();
+        trusted.apply_pencilmark_policy(PencilmarkPolicy::Trust);
+        assert_eq!(trusted.pencilmarks[2], set!(4u8));
+        assert_eq!(trusted.pencilmarks[4], one_to_nine());
+
+        let mut recomputed = board.clone();
+        recomputed.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        assert!(!recomputed.pencilmarks[2].is_empty());
+        assert!(!recomputed.pencilmarks[2].contains(&4));
+        assert!(!recomputed.pencilmarks[4].contains(&4));
+
+        let mut intersected = board.clone();
+        intersected.apply_pencilmark_policy(PencilmarkPolicy::Intersect);
+        assert!(intersected.pencilmarks[2].is_empty());
+        assert_eq!(intersected.pencilmarks[4], recomputed.pencilmarks[4]);
+    }
+    #[test]
+    fn test_placement_only_touches_peers() {
+        let mut solver =
+            Solver::init_with_board(Board::new(HashMap::new()), PencilmarkPolicy::Trust);
+        solver.dirty = [false; 9];
+        solver.place(0, 7);
+        solver.propagate();
+        let peers = peers(0);
+        assert_eq!(peers.len(), 20);
+        for i in 1..81 {
+            assert_eq!(
+                solver.board.pencilmarks[i].contains(&7),
+                !peers.contains(&i)
+            );
+        }
+        // box 4 (the middle) shares no row or column with cell 0
+        assert!(!solver.dirty[4]);
+        assert!(solver.dirty[0] && solver.dirty[1] && solver.dirty[3]);
+    }
+    #[test]
+    fn test_box_schedules() {
+        let easy = crate::catalog::test_board("nyt-easy");
+        let hard = crate::catalog::test_board("nyt-hard");
+        let schedules = [
+            BoxSchedule::RoundRobin,
+            BoxSchedule::MostFilled,
+            BoxSchedule::MostRecentChange,
+            BoxSchedule::Global,
+        ];
+        for schedule in schedules.iter() {
+            let solver = Solver::init_with_board(easy.clone(), PencilmarkPolicy::Recompute);
+            let solved = solve_with_solver(solver.with_schedule(*schedule));
+            assert!(solved.digits.iter().all(|digit| digit.is_some()));
+            assert_eq!(crate::search::count_solutions(&solved, 2), 1);
+
+            // the simple solver can't finish the hard board, but it has to stop trying
+            let solver = Solver::init_with_board(hard.clone(), PencilmarkPolicy::Recompute);
+            solve_with_solver(solver.with_schedule(*schedule));
+        }
+    }
+    #[test]
+    fn test_solve_budget() {
+        let easy = crate::catalog::test_board("nyt-easy");
+        let solver = Solver::init_with_board(easy, PencilmarkPolicy::Recompute);
+        let outcome = solve_within(solver.clone(), &Budget::unlimited().with_max_steps(3));
+        assert_eq!(outcome.stopped, crate::budget::StopReason::StepLimit);
+        assert_eq!(outcome.steps, 3);
+        assert!(outcome.result.digits.iter().any(|digit| digit.is_none()));
+
+        let outcome = solve_within(solver, &Budget::unlimited());
+        assert!(outcome.is_complete());
+        assert!(outcome.result.digits.iter().all(|digit| digit.is_some()));
+    }
+    #[test]
+    fn test_line_format() {
+        let line =
+            "48.1..6.9..7.8.1.55.6.472....8..1...6.28735..7...6.9.3.746.8.9.86.7.2....3.....67";
+        let board: Board = line.parse().unwrap();
+        assert_eq!(board.digits, crate::catalog::test_board("nyt-easy").digits);
+        assert_eq!(board.to_line(), line);
+        let zeros: Board = format!("  {}\n", line.replace('.', "0")).parse().unwrap();
+        assert_eq!(zeros.to_line(), line);
+        assert!(line[1..].parse::<Board>().is_err());
+        assert!(line.replace('.', "x").parse::<Board>().is_err());
+    }
 }
@@ -0,0 +1,279 @@
+use crate::board::Board;
+use crate::catalog::Entry;
+use crate::generate::Generated;
+use crate::grade::Difficulty;
+use crate::pdf::{self, Font, Page};
+use crate::search::{self, Solutions};
+
+// Printable packets of puzzles: a few puzzles to a page, each with its number, title and
+// difficulty, then an answer key with the solutions smaller and more to a page.
+
+pub struct BookletPuzzle {
+    pub title: String,
+    pub difficulty: Option<Difficulty>,
+    pub puzzle: Board,
+    // Left out of the answer key when missing.
+    pub solution: Option<Board>,
+}
+
+impl BookletPuzzle {
+    // The solution is worked out here, as the catalog doesn't store one.
+    pub fn from_entry(entry: &Entry) -> BookletPuzzle {
+        let puzzle = entry.board();
+        let solution = match search::unique_solution(&puzzle) {
+            Solutions::Unique(solution) => Some(*solution),
+            _ => None,
+        };
+        return BookletPuzzle {
+            title: entry.title.clone(),
+            difficulty: entry.difficulty,
+            puzzle,
+            solution,
+        };
+    }
+    pub fn from_generated(generated: &Generated, title: &str) -> BookletPuzzle {
+        return BookletPuzzle {
+            title: title.to_string(),
+            difficulty: Some(generated.grade.difficulty),
+            puzzle: generated.puzzle.clone(),
+            solution: Some(generated.solution.clone()),
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageSize {
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    fn points(self) -> (f64, f64) {
+        return match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+        };
+    }
+}
+
+impl std::str::FromStr for PageSize {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PageSize, String> {
+        return match s {
+            "a4" => Ok(PageSize::A4),
+            "letter" => Ok(PageSize::Letter),
+            _ => Err(format!("unknown page size: {}", s)),
+        };
+    }
+}
+
+pub struct BookletOptions {
+    pub title: String,
+    pub per_page: usize,
+    pub answers_per_page: usize,
+    pub page_size: PageSize,
+    pub answer_key: bool,
+}
+
+impl BookletOptions {
+    pub fn new(title: &str) -> BookletOptions {
+        return BookletOptions {
+            title: title.to_string(),
+            per_page: 4,
+            answers_per_page: 12,
+            page_size: PageSize::A4,
+            answer_key: true,
+        };
+    }
+}
+
+fn difficulty_label(difficulty: Difficulty) -> &'static str {
+    return match difficulty {
+        Difficulty::Easy => "Easy",
+        Difficulty::Medium => "Medium",
+        Difficulty::Hard => "Hard",
+        Difficulty::Expert => "Expert",
+        Difficulty::Fiendish => "Fiendish",
+    };
+}
+
+// Columns and rows for `count` grids on a portrait page.
+fn arrangement(count: usize) -> (usize, usize) {
+    return match count {
+        0 | 1 => (1, 1),
+        2 => (1, 2),
+        3 | 4 => (2, 2),
+        5 | 6 => (2, 3),
+        _ => {
+            let cols = ((count as f64) / 1.4).sqrt().ceil() as usize;
+            (cols, count.div_ceil(cols))
+        }
+    };
+}
+
+// Draws a grid with its top left corner at (`left`, `top`). Digits that aren't in `givens` are
+// set in the regular weight, so an answer shows which digits were there to begin with.
+fn draw_grid(page: &mut Page, left: f64, top: f64, side: f64, board: &Board, givens: &Board) {
+    let cell = side / 9.0;
+    for i in 0..10 {
+        let width = if i % 3 == 0 {
+            side / 150.0
+        } else {
+            side / 600.0
+        };
+        let at = i as f64 * cell;
+        page.line(left + at, top, left + at, top - side, width);
+        page.line(left, top - at, left + side, top - at, width);
+    }
+    let size = cell * 0.6;
+    for idx in 0..81 {
+        if let Some(digit) = board.digit(idx) {
+            let font = if givens.digit(idx).is_some() {
+                Font::Bold
+            } else {
+                Font::Regular
+            };
+            let x = left + (idx % 9) as f64 * cell + cell / 2.0;
+            // Digits sit on the baseline and stand about 0.7 of the font size tall.
+            let y = top - (idx / 9) as f64 * cell - cell / 2.0 - size * 0.35;
+            page.centred_text(x, y, size, font, &digit.to_string());
+        }
+    }
+}
+
+// Lays out `grids` (label, board, givens) across as many pages as it takes.
+fn grid_pages(
+    options: &BookletOptions,
+    heading: &str,
+    per_page: usize,
+    grids: &[(String, &Board, &Board)],
+    first_page: usize,
+) -> Vec<Page> {
+    let (width, height) = options.page_size.points();
+    let margin = 40.0;
+    let heading_size = 18.0;
+    let (cols, rows) = arrangement(per_page);
+    let slot_width = (width - 2.0 * margin) / cols as f64;
+    let slot_height = (height - 2.0 * margin - 2.0 * heading_size) / rows as f64;
+    let label_size = (slot_height / 25.0).clamp(7.0, 12.0);
+    let side = (slot_width - 20.0).min(slot_height - 3.0 * label_size);
+
+    let mut pages = Vec::<Page>::new();
+    for (page_index, chunk) in grids.chunks(per_page.max(1)).enumerate() {
+        let mut page = Page::new(width, height);
+        page.centred_text(
+            width / 2.0,
+            height - margin - heading_size,
+            heading_size,
+            Font::Bold,
+            heading,
+        );
+        page.centred_text(
+            width / 2.0,
+            margin / 2.0,
+            9.0,
+            Font::Regular,
+            &(first_page + page_index).to_string(),
+        );
+        for (i, (label, board, givens)) in chunk.iter().enumerate() {
+            let slot_left = margin + (i % cols) as f64 * slot_width;
+            let slot_top = height - margin - 2.0 * heading_size - (i / cols) as f64 * slot_height;
+            let left = slot_left + (slot_width - side) / 2.0;
+            page.text(
+                left,
+                slot_top - label_size * 1.5,
+                label_size,
+                Font::Bold,
+                label,
+            );
+            draw_grid(
+                &mut page,
+                left,
+                slot_top - label_size * 2.5,
+                side,
+                board,
+                givens,
+            );
+        }
+        pages.push(page);
+    }
+    return pages;
+}
+
+pub fn booklet(puzzles: &[BookletPuzzle], options: &BookletOptions) -> Vec<u8> {
+    let labelled: Vec<(String, &Board, &Board)> = puzzles
+        .iter()
+        .enumerate()
+        .map(|(i, puzzle)| {
+            let label = match puzzle.difficulty {
+                Some(difficulty) => {
+                    format!(
+                        "{}. {} - {}",
+                        i + 1,
+                        puzzle.title,
+                        difficulty_label(difficulty)
+                    )
+                }
+                None => format!("{}. {}", i + 1, puzzle.title),
+            };
+            (label, &puzzle.puzzle, &puzzle.puzzle)
+        })
+        .collect();
+    let mut pages = grid_pages(options, &options.title, options.per_page, &labelled, 1);
+    if options.answer_key {
+        let answers: Vec<(String, &Board, &Board)> = puzzles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, puzzle)| {
+                let solution = puzzle.solution.as_ref()?;
+                Some((
+                    format!("{}. {}", i + 1, puzzle.title),
+                    solution,
+                    &puzzle.puzzle,
+                ))
+            })
+            .collect();
+        let heading = format!("{} - Answers", options.title);
+        let first_page = pages.len() + 1;
+        pages.extend(grid_pages(
+            options,
+            &heading,
+            options.answers_per_page,
+            &answers,
+            first_page,
+        ));
+    }
+    return pdf::write(&pages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{self, GenerateOptions};
+
+    #[test]
+    fn test_booklet() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/puzzles");
+        let catalog = crate::catalog::Catalog::load(&dir).unwrap();
+        let mut puzzles: Vec<BookletPuzzle> =
+            catalog.entries().map(BookletPuzzle::from_entry).collect();
+        let generated = generate::generate(&GenerateOptions::new(Difficulty::Easy).with_seed(45));
+        puzzles.push(BookletPuzzle::from_generated(
+            &generated.unwrap(),
+            "Warm-up",
+        ));
+        assert!(puzzles.iter().all(|puzzle| puzzle.solution.is_some()));
+
+        let mut options = BookletOptions::new("Club packet");
+        options.per_page = 2;
+        let pdf = String::from_utf8(booklet(&puzzles, &options)).unwrap();
+        // five puzzles two to a page, then one page of answers
+        assert!(pdf.contains("/Count 4 "));
+        assert!(pdf.contains("(5. Warm-up - Easy) Tj"));
+        assert!(pdf.contains("(Club packet - Answers) Tj"));
+
+        options.answer_key = false;
+        let pdf = String::from_utf8(booklet(&puzzles, &options)).unwrap();
+        assert!(pdf.contains("/Count 3 "));
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Limits on how much work a solving call may do before it gives up and hands back whatever it
+// has so far. A step is one box worked by the naturalistic solver; a node is one digit tried by
+// the exhaustive search.
+
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        return CancelToken::default();
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    pub max_nodes: Option<u64>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl Budget {
+    pub fn unlimited() -> Budget {
+        return Budget::default();
+    }
+    pub fn with_max_steps(mut self, max_steps: u64) -> Budget {
+        self.max_steps = Some(max_steps);
+        return self;
+    }
+    pub fn with_max_nodes(mut self, max_nodes: u64) -> Budget {
+        self.max_nodes = Some(max_nodes);
+        return self;
+    }
+    pub fn with_timeout(mut self, timeout: Duration) -> Budget {
+        self.deadline = Some(Instant::now() + timeout);
+        return self;
+    }
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Budget {
+        self.cancel = Some(cancel);
+        return self;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    // Ran until there was nothing left to do, whether or not that means solved.
+    Completed,
+    StepLimit,
+    NodeLimit,
+    Deadline,
+    Cancelled,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            StopReason::Completed => "completed",
+            StopReason::StepLimit => "step_limit",
+            StopReason::NodeLimit => "node_limit",
+            StopReason::Deadline => "deadline",
+            StopReason::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A possibly partial result, along with why the work stopped and how much of it was done.
+pub struct Outcome<T> {
+    pub result: T,
+    pub stopped: StopReason,
+    pub steps: u64,
+    pub nodes: u64,
+}
+
+impl<T> Outcome<T> {
+    pub fn is_complete(&self) -> bool {
+        return self.stopped == StopReason::Completed;
+    }
+}
+
+// The deadline and cancellation token are only looked at every this many nodes, since the
+// search visits far more nodes than the naturalistic solver takes steps.
+const NODES_PER_CLOCK_CHECK: u64 = 256;
+
+pub struct Meter<'a> {
+    budget: &'a Budget,
+    steps: u64,
+    nodes: u64,
+}
+
+impl<'a> Meter<'a> {
+    pub fn new(budget: &'a Budget) -> Meter<'a> {
+        return Meter {
+            budget,
+            steps: 0,
+            nodes: 0,
+        };
+    }
+    fn check_clock(&self) -> Option<StopReason> {
+        if let Some(cancel) = &self.budget.cancel {
+            if cancel.is_cancelled() {
+                return Some(StopReason::Cancelled);
+            }
+        }
+        if let Some(deadline) = self.budget.deadline {
+            if Instant::now() >= deadline {
+                return Some(StopReason::Deadline);
+            }
+        }
+        return None;
+    }
+    // Charge one step, returning the reason to stop if the budget is spent.
+    pub fn step(&mut self) -> Option<StopReason> {
+        if let Some(max_steps) = self.budget.max_steps {
+            if self.steps >= max_steps {
+                return Some(StopReason::StepLimit);
+            }
+        }
+        self.steps += 1;
+        return self.check_clock();
+    }
+    pub fn node(&mut self) -> Option<StopReason> {
+        if let Some(max_nodes) = self.budget.max_nodes {
+            if self.nodes >= max_nodes {
+                return Some(StopReason::NodeLimit);
+            }
+        }
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) {
+            return self.check_clock();
+        }
+        return None;
+    }
+    pub fn finish<T>(&self, result: T, stopped: Option<StopReason>) -> Outcome<T> {
+        return Outcome {
+            result,
+            stopped: stopped.unwrap_or(StopReason::Completed),
+            steps: self.steps,
+            nodes: self.nodes,
+        };
+    }
+}
@@ -0,0 +1,255 @@
+use crate::board::Board;
+use std::collections::HashSet;
+
+// Positions with pencilmarks, in the two forms people paste around.
+//
+// The candidate grid is what HoDoKu and SudokuWiki print: one whitespace-separated group of
+// digits per cell, laid out in rows with box borders drawn in `.-:|+*'`. The 729-character
+// string gives each cell nine places, the `n`th holding `n` when it is a candidate and `.` or
+// `0` when it isn't.
+//
+// Neither form marks which digits are placed, so both follow HoDoKu in treating a cell with
+// exactly one digit as placed. That loses a distinction: a lone candidate that hasn't been
+// filled in reads back as a placement, and a board that needs to keep it has to travel as
+// `SquareState`s instead. A cell with no candidates at all is written `0` in the grid.
+
+fn board_from_cells(cells: Vec<HashSet<u8>>) -> Board {
+    let mut digits = [None; 81];
+    for (digit, cell) in digits.iter_mut().zip(cells.iter()) {
+        if cell.len() == 1 {
+            *digit = cell.iter().next().cloned();
+        }
+    }
+    let mut board = Board::from_digits(digits);
+    for (idx, cell) in cells.into_iter().enumerate() {
+        if digits[idx].is_none() {
+            board.set_pencilmarks(idx, cell);
+        }
+    }
+    return board;
+}
+
+fn sorted(pencilmarks: &HashSet<u8>) -> Vec<u8> {
+    let mut digits: Vec<u8> = pencilmarks.iter().cloned().collect();
+    digits.sort_unstable();
+    return digits;
+}
+
+// What gets written for a cell: its digit, or its candidates in order.
+fn cell_text(board: &Board, idx: usize) -> String {
+    if let Some(digit) = board.digit(idx) {
+        return digit.to_string();
+    }
+    let digits = sorted(board.pencilmarks(idx));
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    return digits.iter().map(|digit| digit.to_string()).collect();
+}
+
+pub fn parse_grid(s: &str) -> Result<Board, String> {
+    let mut cells = Vec::<HashSet<u8>>::with_capacity(81);
+    let mut count = 0;
+    for token in s.split(|ch: char| !ch.is_ascii_digit()) {
+        if token.is_empty() {
+            continue;
+        }
+        if count < 81 {
+            let mut digits = HashSet::<u8>::new();
+            for ch in token.chars().filter(|ch| *ch != '0') {
+                if !digits.insert(ch as u8 - b'0') {
+                    return Err(format!("candidate {} repeated in cell {}", ch, count));
+                }
+            }
+            cells.push(digits);
+        }
+        count += 1;
+    }
+    if count != 81 {
+        return Err(format!("candidate grid has {} cells, expected 81", count));
+    }
+    return Ok(board_from_cells(cells));
+}
+
+// The HoDoKu layout, with each column as wide as its widest cell.
+pub fn format_grid(board: &Board) -> String {
+    let cells: Vec<String> = (0..81).map(|idx| cell_text(board, idx)).collect();
+    let mut widths = [0; 9];
+    for (idx, cell) in cells.iter().enumerate() {
+        widths[idx % 9] = widths[idx % 9].max(cell.len());
+    }
+    let border = |left: char, middle: char, right: char| {
+        let mut line = String::new();
+        for stack in 0..3 {
+            line.push(if stack == 0 { left } else { middle });
+            let inner: usize = widths[3 * stack..3 * stack + 3].iter().sum::<usize>() + 6;
+            line.push_str(&"-".repeat(inner));
+        }
+        line.push(right);
+        return line;
+    };
+    let mut lines = vec![border('.', '.', '.')];
+    for row in 0..9 {
+        if row == 3 || row == 6 {
+            lines.push(border(':', '+', ':'));
+        }
+        let mut line = String::new();
+        for col in 0..9 {
+            if col % 3 == 0 {
+                line.push_str("| ");
+            }
+            line.push_str(&format!(
+                "{:<width$}",
+                cells[9 * row + col],
+                width = widths[col]
+            ));
+            line.push(' ');
+            if col % 3 != 2 {
+                line.push(' ');
+            }
+        }
+        line.push('|');
+        lines.push(line);
+    }
+    lines.push(border('\'', '\'', '\''));
+    return lines.join("\n");
+}
+
+pub fn parse_line(s: &str) -> Result<Board, String> {
+    let chars: Vec<char> = s.trim().chars().collect();
+    if chars.len() != 729 {
+        return Err(format!(
+            "candidate string has {} characters, expected 729",
+            chars.len()
+        ));
+    }
+    let mut cells = Vec::<HashSet<u8>>::with_capacity(81);
+    for (idx, places) in chars.chunks(9).enumerate() {
+        let mut digits = HashSet::<u8>::new();
+        for (i, ch) in places.iter().enumerate() {
+            let digit = i as u8 + 1;
+            match ch {
+                '.' | '0' => {}
+                _ if *ch == (b'0' + digit) as char => {
+                    digits.insert(digit);
+                }
+                _ => {
+                    return Err(format!(
+                        "unexpected {} in place {} of cell {}",
+                        ch,
+                        i + 1,
+                        idx
+                    ))
+                }
+            }
+        }
+        cells.push(digits);
+    }
+    return Ok(board_from_cells(cells));
+}
+
+pub fn format_line(board: &Board) -> String {
+    let mut line = String::with_capacity(729);
+    for idx in 0..81 {
+        let digits: Vec<u8> = match board.digit(idx) {
+            Some(digit) => vec![digit],
+            None => sorted(board.pencilmarks(idx)),
+        };
+        for digit in 1..10u8 {
+            line.push(if digits.contains(&digit) {
+                (b'0' + digit) as char
+            } else {
+                '.'
+            });
+        }
+    }
+    return line;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PencilmarkPolicy;
+
+    fn same_position(a: &Board, b: &Board) -> bool {
+        return (0..81).all(|idx| {
+            a.digit(idx) == b.digit(idx)
+                && (a.digit(idx).is_some() || a.pencilmarks(idx) == b.pencilmarks(idx))
+        });
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let mut board = crate::catalog::test_board("nyt-hard");
+        board.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        // a cell nobody has any candidates left for still has to survive the trip
+        board.set_pencilmarks(0, HashSet::new());
+        // cell 13 is down to one candidate, which can't survive the trip; see
+        // `test_lone_candidates_read_as_placed`
+        let digit = *board.pencilmarks(13).iter().next().unwrap();
+        board.set_pencilmarks(13, [digit, digit % 9 + 1].iter().cloned().collect());
+
+        let grid = format_grid(&board);
+        assert!(grid.starts_with(".---"));
+        assert_eq!(grid.lines().count(), 13);
+        assert!(same_position(&parse_grid(&grid).unwrap(), &board));
+
+        let line = format_line(&board);
+        assert_eq!(line.len(), 729);
+        assert!(same_position(&parse_line(&line).unwrap(), &board));
+        assert!(same_position(
+            &parse_line(&line.replace('.', "0")).unwrap(),
+            &board
+        ));
+    }
+
+    #[test]
+    fn test_lone_candidates_read_as_placed() {
+        let mut board = crate::catalog::test_board("nyt-hard");
+        board.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        assert_eq!(board.pencilmarks(13).len(), 1);
+        let digit = *board.pencilmarks(13).iter().next().unwrap();
+        assert_eq!(board.digit(13), None);
+        // the documented loss, and the only one: every other cell comes back as it was
+        for read in [
+            parse_grid(&format_grid(&board)).unwrap(),
+            parse_line(&format_line(&board)).unwrap(),
+        ] {
+            assert_eq!(read.digit(13), Some(digit));
+            for idx in 0..81 {
+                match (board.digit(idx), board.pencilmarks(idx).len()) {
+                    (None, 1) => assert_eq!(
+                        read.digit(idx),
+                        board.pencilmarks(idx).iter().next().cloned()
+                    ),
+                    (None, _) => assert_eq!(read.pencilmarks(idx), board.pencilmarks(idx)),
+                    (placed, _) => assert_eq!(read.digit(idx), placed),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pasted_grids() {
+        // SudokuWiki draws its borders with `*`; only the digit groups matter.
+        let mut pasted = String::from("*-----------*\n");
+        for row in 0..9 {
+            let cells: Vec<String> = (0..9)
+                .map(|col| match (row + col) % 9 {
+                    0 => "5".to_string(),
+                    _ => "1234".to_string(),
+                })
+                .collect();
+            pasted.push_str(&format!("| {} |\n", cells.join(" ")));
+        }
+        let board = parse_grid(&pasted).unwrap();
+        assert_eq!(board.digit(0), Some(5));
+        assert_eq!(board.digit(1), None);
+        assert_eq!(sorted(board.pencilmarks(1)), vec![1, 2, 3, 4]);
+
+        assert!(parse_grid("1 2 3").is_err());
+        assert!(parse_grid(&"11 ".repeat(81)).is_err());
+        assert!(parse_line(&"x".repeat(729)).is_err());
+        assert!(parse_line(&"2".repeat(729)).is_err());
+    }
+}
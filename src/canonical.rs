@@ -0,0 +1,185 @@
+use crate::board::Board;
+
+// Two puzzles are the same puzzle if one of the transformations in `transform` turns one into
+// the other. The canonical form picks one representative of each such family: the arrangement
+// whose clues, read across then down with blanks as 0 and digits renumbered in order of first
+// appearance, come first in lexicographic order. Pencilmarks play no part.
+//
+// Digits renumbered by first appearance already make the smallest string for a given
+// arrangement of cells, so only the cell arrangements have to be searched: transposed or not,
+// every column order that respects stacks, and every row order that respects bands. Row orders
+// are built a row at a time and abandoned as soon as they read worse than the best so far.
+
+type Grid = [u8; 81];
+
+fn grid_of(board: &Board) -> Grid {
+    let mut grid = [0; 81];
+    for (idx, cell) in grid.iter_mut().enumerate() {
+        *cell = board.digit(idx).unwrap_or(0);
+    }
+    return grid;
+}
+
+fn permutations_of_three() -> [[usize; 3]; 6] {
+    return [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ];
+}
+
+// Every column order that keeps the stacks together.
+fn column_orders() -> Vec<[usize; 9]> {
+    let perms = permutations_of_three();
+    let mut orders = Vec::<[usize; 9]>::with_capacity(1296);
+    for stacks in perms.iter() {
+        for first in perms.iter() {
+            for second in perms.iter() {
+                for third in perms.iter() {
+                    let within = [first, second, third];
+                    let mut order = [0; 9];
+                    for (i, col) in order.iter_mut().enumerate() {
+                        *col = 3 * stacks[i / 3] + within[i / 3][i % 3];
+                    }
+                    orders.push(order);
+                }
+            }
+        }
+    }
+    return orders;
+}
+
+struct Search<'a> {
+    grid: &'a Grid,
+    cols: [usize; 9],
+    rows: Vec<usize>,
+    best: Option<Grid>,
+}
+
+impl Search<'_> {
+    // The candidate so far, for the rows chosen, with digits renumbered by first appearance.
+    fn relabelled(&self) -> Vec<u8> {
+        let mut labels = [0u8; 10];
+        let mut next = 1;
+        let mut cells = Vec::<u8>::with_capacity(9 * self.rows.len());
+        for row in &self.rows {
+            for col in &self.cols {
+                let digit = self.grid[9 * row + col] as usize;
+                if digit != 0 && labels[digit] == 0 {
+                    labels[digit] = next;
+                    next += 1;
+                }
+                cells.push(labels[digit]);
+            }
+        }
+        return cells;
+    }
+    fn extend(&mut self) {
+        let prefix = self.relabelled();
+        if let Some(best) = &self.best {
+            if prefix[..] > best[..prefix.len()] {
+                return;
+            }
+        }
+        if self.rows.len() == 9 {
+            let mut candidate = [0; 81];
+            candidate.copy_from_slice(&prefix);
+            if self.best.is_none_or(|best| candidate < best) {
+                self.best = Some(candidate);
+            }
+            return;
+        }
+        // A new band can start with any row of an unused band; otherwise the row has to come
+        // from the band already started.
+        let choices: Vec<usize> = if self.rows.len().is_multiple_of(3) {
+            (0..9)
+                .filter(|row| self.rows.iter().all(|used| used / 3 != row / 3))
+                .collect()
+        } else {
+            let band = self.rows[self.rows.len() - 1] / 3;
+            (3 * band..3 * band + 3)
+                .filter(|row| !self.rows.contains(row))
+                .collect()
+        };
+        for row in choices {
+            self.rows.push(row);
+            self.extend();
+            self.rows.pop();
+        }
+    }
+}
+
+fn canonical_grid(board: &Board) -> Grid {
+    let grid = grid_of(board);
+    let mut transposed = [0; 81];
+    for (idx, cell) in transposed.iter_mut().enumerate() {
+        *cell = grid[9 * (idx % 9) + idx / 9];
+    }
+    let mut best: Option<Grid> = None;
+    for grid in [grid, transposed].iter() {
+        for cols in column_orders() {
+            let mut search = Search {
+                grid,
+                cols,
+                rows: Vec::with_capacity(9),
+                best,
+            };
+            search.extend();
+            best = search.best;
+        }
+    }
+    return best.unwrap();
+}
+
+pub fn canonical_form(board: &Board) -> Board {
+    let mut digits = [None; 81];
+    for (digit, cell) in digits.iter_mut().zip(canonical_grid(board).iter()) {
+        if *cell != 0 {
+            *digit = Some(*cell);
+        }
+    }
+    return Board::from_digits(digits);
+}
+
+// The canonical form as 81 characters, `.` for a blank.
+pub fn canonical_string(board: &Board) -> String {
+    return canonical_form(board).to_line();
+}
+
+// A 64-bit FNV-1a hash of the canonical string. It is spelled out here rather than taken from
+// `std::hash` so the value stays the same across Rust versions and can be stored.
+pub fn canonical_hash(board: &Board) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in canonical_string(board).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    return hash;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::transform::Transform;
+
+    #[test]
+    fn test_equivalent_puzzles_share_a_form() {
+        let easy = crate::catalog::test_board("nyt-easy");
+        let hard = crate::catalog::test_board("nyt-hard");
+        let form = canonical_string(&easy);
+        assert_eq!(form.len(), 81);
+        assert_eq!(canonical_string(&canonical_form(&easy)), form);
+        assert_ne!(canonical_hash(&easy), canonical_hash(&hard));
+
+        let mut rng = Rng::new(38);
+        for _ in 0..3 {
+            let disguised = Transform::random(&mut rng).apply(&easy);
+            assert_eq!(canonical_string(&disguised), form);
+            assert_eq!(canonical_hash(&disguised), canonical_hash(&easy));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::board::Board;
+use crate::canonical;
+use crate::grade::Difficulty;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Puzzles live as JSON collections in a data directory, one file per collection:
+//
+//     { "id": "nyt", "title": "New York Times", "source": "...",
+//       "puzzles": [ { "id": "nyt-easy", "title": "NYT easy", "tags": ["newspaper"],
+//                      "difficulty": "easy", "givens": "48.1..6.9..7.8.1.55.6..." } ] }
+//
+// `givens` is the grid read across then down, with `.` or `0` for a blank. Puzzle IDs are
+// unique across the whole catalog.
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    // Falls back to the collection's source when a puzzle doesn't name its own.
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+    pub givens: String,
+    // The ID of the collection the puzzle came from, filled in on load.
+    #[serde(default)]
+    pub collection: String,
+    #[serde(skip)]
+    digits: Vec<Option<u8>>,
+}
+
+impl Entry {
+    pub fn board(&self) -> Board {
+        let mut digits = [None; 81];
+        digits.copy_from_slice(&self.digits);
+        return Board::from_digits(digits);
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Collection {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub source: Option<String>,
+    pub puzzles: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub enum CatalogError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    BadGivens(String, String),
+    DuplicateId(String),
+}
+
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CatalogError::Io(path, err) => write!(f, "couldn't read {}: {}", path.display(), err),
+            CatalogError::Parse(path, err) => {
+                write!(f, "couldn't parse {}: {}", path.display(), err)
+            }
+            CatalogError::BadGivens(id, err) => write!(f, "puzzle {} has bad givens: {}", id, err),
+            CatalogError::DuplicateId(id) => write!(f, "puzzle ID {} is used twice", id),
+        }
+    }
+}
+
+// Which puzzles to list. Unset fields match everything.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub collection: Option<String>,
+    pub tag: Option<String>,
+    pub difficulty: Option<Difficulty>,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if let Some(collection) = &self.collection {
+            if &entry.collection != collection {
+                return false;
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if !entry.tags.contains(tag) {
+                return false;
+            }
+        }
+        if self.difficulty.is_some() && entry.difficulty != self.difficulty {
+            return false;
+        }
+        return true;
+    }
+}
+
+fn parse_givens(givens: &str) -> Result<Vec<Option<u8>>, String> {
+    let board: Board = givens.parse()?;
+    return Ok((0..81).map(|idx| board.digit(idx)).collect());
+}
+
+// Where the server and binary look for puzzles: `$SUDOKU_PUZZLES` if set, otherwise
+// `data/puzzles` under the working directory.
+pub fn default_dir() -> PathBuf {
+    return match std::env::var_os("SUDOKU_PUZZLES") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from("data/puzzles"),
+    };
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    collections: Vec<Collection>,
+}
+
+impl Catalog {
+    // Reads every `.json` file in `dir`, in file name order.
+    pub fn load(dir: &Path) -> Result<Catalog, CatalogError> {
+        let read_dir = std::fs::read_dir(dir).map_err(|err| CatalogError::Io(dir.into(), err))?;
+        let mut paths = Vec::<PathBuf>::new();
+        for dir_entry in read_dir {
+            let path = dir_entry
+                .map_err(|err| CatalogError::Io(dir.into(), err))?
+                .path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let mut catalog = Catalog::default();
+        for path in paths {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| CatalogError::Io(path.clone(), err))?;
+            let collection: Collection = serde_json::from_str(&text)
+                .map_err(|err| CatalogError::Parse(path.clone(), err.to_string()))?;
+            catalog.add(collection)?;
+        }
+        return Ok(catalog);
+    }
+    pub fn add(&mut self, mut collection: Collection) -> Result<(), CatalogError> {
+        let mut seen = std::collections::HashSet::<String>::new();
+        for entry in collection.puzzles.iter_mut() {
+            if self.get(&entry.id).is_some() || !seen.insert(entry.id.clone()) {
+                return Err(CatalogError::DuplicateId(entry.id.clone()));
+            }
+            entry.digits = parse_givens(&entry.givens)
+                .map_err(|err| CatalogError::BadGivens(entry.id.clone(), err))?;
+            entry.collection = collection.id.clone();
+            if entry.source.is_none() {
+                entry.source = collection.source.clone();
+            }
+        }
+        self.collections.push(collection);
+        return Ok(());
+    }
+    pub fn collections(&self) -> &[Collection] {
+        return &self.collections;
+    }
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        return self
+            .collections
+            .iter()
+            .flat_map(|collection| collection.puzzles.iter());
+    }
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        return self.entries().find(|entry| entry.id == id);
+    }
+    // Groups of puzzles that are the same up to symmetry and relabelling, in catalog order.
+    // Puzzles without a double are left out.
+    pub fn duplicates(&self) -> Vec<Vec<&Entry>> {
+        let mut groups = Vec::<Vec<&Entry>>::new();
+        // Canonical form to the index of its group, which keeps the groups in catalog order.
+        let mut seen = HashMap::<String, usize>::new();
+        for entry in self.entries() {
+            let form = canonical::canonical_string(&entry.board());
+            match seen.get(&form) {
+                Some(index) => groups[*index].push(entry),
+                None => {
+                    seen.insert(form, groups.len());
+                    groups.push(vec![entry]);
+                }
+            }
+        }
+        return groups.into_iter().filter(|group| group.len() > 1).collect();
+    }
+    pub fn filter(&self, filter: &Filter) -> Vec<&Entry> {
+        return self
+            .entries()
+            .filter(|entry| filter.matches(entry))
+            .collect();
+    }
+}
+
+// The puzzles shipped with the crate, for tests that want a known board.
+#[cfg(test)]
+pub(crate) fn test_board(id: &str) -> Board {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/puzzles");
+    return Catalog::load(&dir).unwrap().get(id).unwrap().board();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search;
+
+    #[test]
+    fn test_shipped_catalog() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/puzzles");
+        let catalog = Catalog::load(&dir).unwrap();
+        for entry in catalog.entries() {
+            assert_eq!(
+                search::count_solutions(&entry.board(), 2),
+                1,
+                "{}",
+                entry.id
+            );
+            assert!(entry.source.is_some());
+        }
+        let easy = catalog.get("nyt-easy").unwrap();
+        assert_eq!(easy.collection, "nyt");
+        assert_eq!(easy.board().digit(0), Some(4));
+        assert!(catalog.get("nyt-medium").is_none());
+
+        let filter = Filter {
+            tag: Some("newspaper".to_string()),
+            difficulty: Some(Difficulty::Hard),
+            ..Filter::default()
+        };
+        let found: Vec<&str> = catalog
+            .filter(&filter)
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(found, vec!["nyt-hard"]);
+    }
+
+    fn single(id: &str, givens: &str) -> Collection {
+        return Collection {
+            id: format!("{}-collection", id),
+            title: "Test".to_string(),
+            source: None,
+            puzzles: vec![Entry {
+                id: id.to_string(),
+                title: "Test".to_string(),
+                source: None,
+                tags: vec![],
+                difficulty: None,
+                givens: givens.to_string(),
+                collection: String::new(),
+                digits: vec![],
+            }],
+        };
+    }
+
+    #[test]
+    fn test_bad_collections() {
+        let mut catalog = Catalog::default();
+        assert!(matches!(
+            catalog.add(single("only", "12x")),
+            Err(CatalogError::BadGivens(_, _))
+        ));
+        catalog.add(single("only", &".".repeat(81))).unwrap();
+        assert!(matches!(
+            catalog.add(single("only", &".".repeat(81))),
+            Err(CatalogError::DuplicateId(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/puzzles");
+        let mut catalog = Catalog::load(&dir).unwrap();
+        assert!(catalog.duplicates().is_empty());
+
+        // the easy NYT board turned upside down with 1 and 2 swapped
+        let relabel = crate::transform::Transform::relabel([2, 1, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        let disguised = crate::transform::Transform::flip()
+            .then(&relabel)
+            .apply(&test_board("nyt-easy"));
+        catalog.add(single("copy", &disguised.to_line())).unwrap();
+        let duplicates = catalog.duplicates();
+        assert_eq!(duplicates.len(), 1);
+        let ids: Vec<&str> = duplicates[0]
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["nyt-easy", "copy"]);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::board::{Board, PencilmarkPolicy};
+use crate::budget::{Budget, StopReason};
+use crate::search::{self, Solutions};
+use serde::Serialize;
+
+// Comparing a player's board against the true solution of the puzzle they started from.
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct WrongDigit {
+    pub idx: usize,
+    pub placed: u8,
+    pub expected: u8,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MissingCandidate {
+    pub idx: usize,
+    pub expected: u8,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Mistakes {
+    pub wrong_digits: Vec<WrongDigit>,
+    pub missing_candidates: Vec<MissingCandidate>,
+}
+
+impl Mistakes {
+    pub fn is_empty(&self) -> bool {
+        return self.wrong_digits.is_empty() && self.missing_candidates.is_empty();
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CheckError {
+    NoSolution,
+    MultipleSolutions,
+    // The budget ran out before the solution was known to be unique.
+    Unfinished(StopReason),
+}
+
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckError::NoSolution => write!(f, "puzzle has no solution"),
+            CheckError::MultipleSolutions => write!(f, "puzzle has more than one solution"),
+            CheckError::Unfinished(reason) => write!(f, "gave up solving the puzzle: {}", reason),
+        }
+    }
+}
+
+// Compares the board exactly as given, so an empty cell with no pencilmarks counts as having
+// removed the correct digit. `find_mistakes` applies a pencilmark policy first.
+pub fn compare_with_solution(player: &Board, solution: &Board) -> Mistakes {
+    let mut mistakes = Mistakes::default();
+    for idx in 0..81 {
+        let expected = match solution.digit(idx) {
+            Some(digit) => digit,
+            None => continue,
+        };
+        match player.digit(idx) {
+            Some(placed) => {
+                if placed != expected {
+                    mistakes.wrong_digits.push(WrongDigit {
+                        idx,
+                        placed,
+                        expected,
+                    });
+                }
+            }
+            None => {
+                let marks = player.pencilmarks(idx);
+                if !marks.contains(&expected) {
+                    mistakes
+                        .missing_candidates
+                        .push(MissingCandidate { idx, expected });
+                }
+            }
+        }
+    }
+    return mistakes;
+}
+
+// Solve `puzzle` (the starting clues) and list everything on `player` that disagrees with it.
+pub fn find_mistakes(
+    puzzle: &Board,
+    player: &Board,
+    policy: PencilmarkPolicy,
+) -> Result<Mistakes, CheckError> {
+    return find_mistakes_within(puzzle, player, policy, &Budget::unlimited());
+}
+
+pub fn find_mistakes_within(
+    puzzle: &Board,
+    player: &Board,
+    policy: PencilmarkPolicy,
+    budget: &Budget,
+) -> Result<Mistakes, CheckError> {
+    let mut player = player.clone();
+    player.apply_pencilmark_policy(policy);
+    let outcome = search::unique_solution_within(puzzle, budget);
+    if !outcome.is_complete() {
+        return Err(CheckError::Unfinished(outcome.stopped));
+    }
+    return match outcome.result {
+        Solutions::None => Err(CheckError::NoSolution),
+        Solutions::Multiple => Err(CheckError::MultipleSolutions),
+        Solutions::Unique(solution) => Ok(compare_with_solution(&player, &solution)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_mistakes_against_solution() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let solution = match search::unique_solution(&puzzle) {
+            Solutions::Unique(solution) => solution,
+            _ => panic!("expected a unique solution"),
+        };
+        let trust = PencilmarkPolicy::Trust;
+        assert!(find_mistakes(&puzzle, &puzzle, trust).unwrap().is_empty());
+        assert!(find_mistakes(&puzzle, &solution, trust).unwrap().is_empty());
+
+        // cell 2 is empty in the easy board
+        let expected = solution.digit(2).unwrap();
+        let wrong = expected % 9 + 1;
+        let mut placed = [None; 81];
+        for (idx, digit) in placed.iter_mut().enumerate() {
+            *digit = puzzle.digit(idx);
+        }
+        placed[2] = Some(wrong);
+        let mistakes = find_mistakes(&puzzle, &Board::from_digits(placed), trust).unwrap();
+        assert_eq!(
+            mistakes.wrong_digits,
+            vec![WrongDigit {
+                idx: 2,
+                placed: wrong,
+                expected
+            }]
+        );
+
+        let mut marks = std::collections::HashSet::new();
+        marks.insert(wrong);
+        let mut penciled = puzzle.clone();
+        penciled.set_pencilmarks(2, marks);
+        let mistakes = find_mistakes(&puzzle, &penciled, trust).unwrap();
+        assert_eq!(
+            mistakes.missing_candidates,
+            vec![MissingCandidate { idx: 2, expected }]
+        );
+        let recomputed = find_mistakes(&puzzle, &penciled, PencilmarkPolicy::Recompute).unwrap();
+        assert!(recomputed.is_empty());
+
+        // with every mark erased, only trusting the client reads the cell as untouched
+        penciled.set_pencilmarks(2, std::collections::HashSet::new());
+        assert!(find_mistakes(&puzzle, &penciled, trust).unwrap().is_empty());
+        let literal = compare_with_solution(&penciled, &solution);
+        assert_eq!(literal.missing_candidates.len(), 1);
+    }
+
+    #[test]
+    fn test_ambiguous_puzzle() {
+        let puzzle = Board::new(HashMap::new());
+        assert_eq!(
+            find_mistakes(&puzzle, &puzzle, PencilmarkPolicy::Trust).unwrap_err(),
+            CheckError::MultipleSolutions
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use crate::budget::{Budget, Meter};
+use crate::generate::{self, GenerateError, GenerateOptions, Generated};
+use crate::grade::Difficulty;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The puzzle of the day is generated from a seed derived from the date and difficulty, so every
+// server hands out the same one without having to store anything.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    // Today in UTC.
+    pub fn today() -> Date {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        return Date::from_days((secs / 86400) as i64);
+    }
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's `civil_from_days`.
+    fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400) as i32 + if month <= 2 { 1 } else { 0 };
+        return Date { year, month, day };
+    }
+    // The inverse of `from_days`, after `days_from_civil`.
+    pub fn to_days(&self) -> i64 {
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        return era * 146_097 + doe - 719_468;
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    return match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+}
+
+impl std::str::FromStr for Date {
+    type Err = String;
+    // Dates are written `YYYY-MM-DD`.
+    fn from_str(s: &str) -> Result<Date, String> {
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() != 3 {
+            return Err(format!("not a YYYY-MM-DD date: {}", s));
+        }
+        let invalid = |_| format!("not a YYYY-MM-DD date: {}", s);
+        let date = Date {
+            year: parts[0].parse().map_err(invalid)?,
+            month: parts[1].parse().map_err(invalid)?,
+            day: parts[2].parse().map_err(invalid)?,
+        };
+        if date.month < 1
+            || date.month > 12
+            || date.day < 1
+            || date.day > days_in_month(date.year, date.month)
+        {
+            return Err(format!("not a YYYY-MM-DD date: {}", s));
+        }
+        return Ok(date);
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+pub fn daily_seed(date: Date, difficulty: Difficulty) -> u64 {
+    let mut seed = (date.year as u64) << 16 | (date.month as u64) << 8 | date.day as u64;
+    seed = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ difficulty as u64;
+    return seed;
+}
+
+// How many seeds a day gets before giving up. Between them they have 1600 attempts, and
+// every difficulty lands well inside that.
+pub const MAX_SEEDS: u64 = 16;
+
+// Seeds that run out of attempts are followed by the next seed along, so the puzzle is still the
+// same one everywhere.
+pub fn daily_puzzle(date: Date, difficulty: Difficulty) -> Result<Generated, GenerateError> {
+    return daily_puzzle_within(date, difficulty, &Budget::unlimited());
+}
+
+// `daily_puzzle` with the budget shared by all the seeds. Running out of it says nothing about
+// the day's puzzle, which a later call with more budget will still find.
+pub fn daily_puzzle_within(
+    date: Date,
+    difficulty: Difficulty,
+    budget: &Budget,
+) -> Result<Generated, GenerateError> {
+    let mut meter = Meter::new(budget);
+    let first = daily_seed(date, difficulty);
+    for seed in (0..MAX_SEEDS).map(|offset| first.wrapping_add(offset)) {
+        let options = GenerateOptions::new(difficulty).with_seed(seed);
+        match generate::generate_metered(&options, &mut meter) {
+            Ok(generated) => return Ok(generated),
+            Err(GenerateError::Unfinished(reason)) => {
+                return Err(GenerateError::Unfinished(reason))
+            }
+            Err(_) => continue,
+        }
+    }
+    return Err(GenerateError::Exhausted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(
+            Date::from_days(0),
+            Date {
+                year: 1970,
+                month: 1,
+                day: 1
+            }
+        );
+        assert_eq!(Date::from_days(20_745).to_string(), "2026-10-19");
+        assert_eq!(Date::from_days(11_016).to_string(), "2000-02-29");
+        assert_eq!("2026-10-19".parse::<Date>(), Ok(Date::from_days(20_745)));
+        assert!("2026-13-01".parse::<Date>().is_err());
+        assert!("2026-02-31".parse::<Date>().is_err());
+        assert!("2026-02-29".parse::<Date>().is_err());
+        assert!("2024-02-29".parse::<Date>().is_ok());
+        for days in [-1, 0, 11_016, 20_745, 100_000] {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+        assert!("yesterday".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn test_daily_puzzle_is_stable() {
+        let date: Date = "2026-10-19".parse().unwrap();
+        let first = daily_puzzle(date, Difficulty::Easy).unwrap();
+        let second = daily_puzzle(date, Difficulty::Easy).unwrap();
+        assert_eq!(first.grade.difficulty, Difficulty::Easy);
+        for idx in 0..81 {
+            assert_eq!(first.puzzle.digit(idx), second.puzzle.digit(idx));
+        }
+        assert_ne!(
+            daily_seed(date, Difficulty::Easy),
+            daily_seed(date, Difficulty::Medium)
+        );
+
+        let tight = Budget::unlimited().with_max_steps(0);
+        assert_eq!(
+            daily_puzzle_within(date, Difficulty::Easy, &tight).err(),
+            Some(GenerateError::Unfinished(
+                crate::budget::StopReason::StepLimit
+            ))
+        );
+    }
+}
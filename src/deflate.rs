@@ -0,0 +1,176 @@
+use crate::inflate::{DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+use crate::png;
+
+// Compression into zlib streams, the other half of `inflate`. Everything goes in one block with
+// the fixed Huffman codes, and repeats are found through hash chains over three-byte prefixes.
+// Rendered boards are long runs of flat colour and rows that repeat the one above, which this
+// catches nearly all of without the cost of building dynamic codes.
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// How many earlier positions with the same prefix are tried before settling for the best so far.
+const MAX_CHAIN: usize = 32;
+
+// Bits go out least significant first, as deflate packs them.
+struct Bits {
+    out: Vec<u8>,
+    value: u64,
+    count: u32,
+}
+
+impl Bits {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.value |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.value as u8);
+            self.value >>= 8;
+            self.count -= 8;
+        }
+    }
+    // Huffman codes are defined most significant bit first, so they go out reversed.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.value as u8);
+        }
+        return self.out;
+    }
+}
+
+// The fixed literal/length code from RFC 1951 section 3.2.6.
+fn write_literal(bits: &mut Bits, symbol: usize) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+// The last code whose base doesn't pass `value`.
+fn code_for(bases: &[u16], value: usize) -> usize {
+    return bases
+        .iter()
+        .rposition(|base| *base as usize <= value)
+        .unwrap();
+}
+
+fn write_match(bits: &mut Bits, length: usize, distance: usize) {
+    let code = code_for(&LENGTH_BASE, length);
+    write_literal(bits, 257 + code);
+    bits.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+    let code = code_for(&DISTANCE_BASE, distance);
+    bits.write_code(code as u32, 5);
+    bits.write(
+        (distance - DISTANCE_BASE[code] as usize) as u32,
+        DISTANCE_EXTRA[code] as u32,
+    );
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let prefix = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    return (prefix.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize;
+}
+
+// Chains in the position at `pos`, if there are enough bytes left for it to start a match.
+fn insert(data: &[u8], pos: usize, head: &mut [usize], previous: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let slot = hash(data, pos);
+        previous[pos % WINDOW] = head[slot];
+        head[slot] = pos;
+    }
+}
+
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits {
+        out: vec![0x78, 0x01],
+        value: 0,
+        count: 0,
+    };
+    // One final block with the fixed codes.
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    // The latest position with each hash, and for each position the one before it.
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let longest = MAX_MATCH.min(data.len() - pos);
+            let mut candidate = head[hash(data, pos)];
+            for _ in 0..MAX_CHAIN {
+                if candidate >= pos || pos - candidate > WINDOW {
+                    break;
+                }
+                let length = (0..longest)
+                    .take_while(|i| data[candidate + i] == data[pos + i])
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = pos - candidate;
+                    if length == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate % WINDOW];
+            }
+        }
+        if best_length >= MIN_MATCH {
+            write_match(&mut bits, best_length, best_distance);
+            for skipped in pos..pos + best_length {
+                insert(data, skipped, &mut head, &mut previous);
+            }
+            pos += best_length;
+        } else {
+            write_literal(&mut bits, data[pos] as usize);
+            insert(data, pos, &mut head, &mut previous);
+            pos += 1;
+        }
+    }
+    write_literal(&mut bits, 256);
+
+    let mut out = bits.finish();
+    out.extend_from_slice(&png::adler32(data).to_be_bytes());
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::zlib_decompress;
+
+    #[test]
+    fn test_zlib_compress() {
+        let mut noisy = Vec::<u8>::new();
+        let mut state = 7u32;
+        for _ in 0..5000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            noisy.push((state >> 24) as u8);
+        }
+        let texts = [
+            vec![],
+            b"a".to_vec(),
+            b"sudoku ".repeat(20),
+            vec![0; 100_000],
+            noisy.clone(),
+            [noisy.clone(), noisy].concat(),
+        ];
+        for text in texts.iter() {
+            let compressed = zlib_compress(text);
+            assert_eq!(&zlib_decompress(&compressed, text.len()).unwrap(), text);
+        }
+        // runs cost a few bits per 258 bytes
+        assert!(zlib_compress(&[0; 100_000]).len() < 1000);
+    }
+}
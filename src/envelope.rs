@@ -0,0 +1,238 @@
+use crate::board::{Board, SquareState};
+use crate::candidates;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+// Boards as clients post them. A request can wrap the board in a versioned envelope that says
+// what format it's in and carries whatever else the client wants to send along:
+//
+//     {"version": 1, "format": "wrapped", "board": {"squares": [...]}, "metadata": {...}}
+//
+// The formats are:
+//
+// - `squares`: the bare array of 81 `SquareState`s, which is what the React app posts
+// - `wrapped`: the app's `TransmittableBoard`, `{"squares": [...]}`
+// - `line`: 81 characters read across then down, `.` or `0` for a blank
+// - `candidates`: the 729-character candidate string
+// - `grid`: a HoDoKu-style candidate grid
+//
+// The two candidate formats can't tell a placed digit from a cell down to one candidate, so a
+// lone candidate in either reads as placed. Posting the board as `squares` or `wrapped` keeps
+// the difference.
+//
+// The envelope is optional, and so is its format tag. Without one the format is worked out from
+// the board itself: JSON arrays and objects by shape, text by how many digits it has. A format
+// that is given is held to rather than second-guessed.
+
+pub const VERSION: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Squares,
+    Wrapped,
+    Line,
+    Candidates,
+    Grid,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Format, String> {
+        return match s {
+            "squares" => Ok(Format::Squares),
+            "wrapped" => Ok(Format::Wrapped),
+            "line" => Ok(Format::Line),
+            "candidates" => Ok(Format::Candidates),
+            "grid" => Ok(Format::Grid),
+            _ => Err(format!("unknown board format: {}", s)),
+        };
+    }
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Format::Squares => "squares",
+            Format::Wrapped => "wrapped",
+            Format::Line => "line",
+            Format::Candidates => "candidates",
+            Format::Grid => "grid",
+        };
+    }
+    pub fn detect(board: &Value) -> Result<Format, String> {
+        return match board {
+            Value::Array(_) => Ok(Format::Squares),
+            Value::Object(fields) if fields.contains_key("squares") => Ok(Format::Wrapped),
+            Value::String(text) => {
+                let marks: Vec<char> = text.chars().filter(|ch| !ch.is_whitespace()).collect();
+                let plain = marks.iter().all(|ch| ch.is_ascii_digit() || *ch == '.');
+                match (plain, marks.len()) {
+                    (true, 81) => Ok(Format::Line),
+                    (true, 729) => Ok(Format::Candidates),
+                    _ => Ok(Format::Grid),
+                }
+            }
+            _ => Err("can't tell what format the board is in".to_string()),
+        };
+    }
+    fn read(&self, board: &Value) -> Result<Board, String> {
+        let squares = match (self, board) {
+            (Format::Squares, _) => board,
+            (Format::Wrapped, Value::Object(fields)) => fields
+                .get("squares")
+                .ok_or("a wrapped board needs squares")?,
+            (Format::Wrapped, _) => return Err("a wrapped board is an object".to_string()),
+            _ => {
+                let text = board
+                    .as_str()
+                    .ok_or(format!("a {} board is a string", self.name()))?;
+                let marks: String = text.chars().filter(|ch| !ch.is_whitespace()).collect();
+                return match self {
+                    Format::Line => marks.parse(),
+                    Format::Candidates => candidates::parse_line(&marks),
+                    _ => candidates::parse_grid(text),
+                };
+            }
+        };
+        let squares: Vec<SquareState> =
+            serde_json::from_value(squares.clone()).map_err(|err| err.to_string())?;
+        return Board::try_from_square_state_vec(squares);
+    }
+}
+
+pub struct Envelope {
+    pub version: u64,
+    pub format: Format,
+    pub board: Board,
+    // Whatever the client sent along with the board, untouched.
+    pub metadata: Map<String, Value>,
+}
+
+// Reads a request body: an envelope, or any of the formats on its own.
+pub fn parse(body: &str) -> Result<Envelope, String> {
+    let trimmed = body.trim();
+    let value = if trimmed.starts_with(['[', '{', '"']) {
+        serde_json::from_str(trimmed).map_err(|err| err.to_string())?
+    } else {
+        Value::String(trimmed.to_string())
+    };
+    let fields = match &value {
+        Value::Object(fields) if fields.contains_key("board") || fields.contains_key("version") => {
+            fields
+        }
+        _ => {
+            let format = Format::detect(&value)?;
+            return Ok(Envelope {
+                version: VERSION,
+                format,
+                board: format.read(&value)?,
+                metadata: Map::new(),
+            });
+        }
+    };
+
+    let version = match fields.get("version") {
+        None => VERSION,
+        Some(version) => version
+            .as_u64()
+            .filter(|version| (1..=VERSION).contains(version))
+            .ok_or(format!("unsupported envelope version: {}", version))?,
+    };
+    let board = fields.get("board").ok_or("envelope has no board")?;
+    let format = match fields.get("format") {
+        None => Format::detect(board)?,
+        Some(Value::String(name)) => name.parse()?,
+        Some(other) => return Err(format!("unknown board format: {}", other)),
+    };
+    let metadata = match fields.get("metadata") {
+        None => Map::new(),
+        Some(Value::Object(metadata)) => metadata.clone(),
+        Some(_) => return Err("envelope metadata must be an object".to_string()),
+    };
+    return Ok(Envelope {
+        version,
+        format,
+        board: format
+            .read(board)
+            .map_err(|err| format!("reading {} board: {}", format.name(), err))?,
+        metadata,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_detect() {
+        let mut puzzle = crate::catalog::test_board("nyt-hard");
+        puzzle.set_pencilmarks(0, [4, 7].iter().cloned().collect());
+        let squares = serde_json::to_string(&puzzle).unwrap();
+        let line = puzzle.to_line();
+        let rows: Vec<&str> = (0..9).map(|row| &line[9 * row..9 * row + 9]).collect();
+        let bodies = [
+            (squares.clone(), Format::Squares),
+            (format!("{{\"squares\": {}}}", squares), Format::Wrapped),
+            (line.clone(), Format::Line),
+            (rows.join("\n"), Format::Line),
+            (json!(line).to_string(), Format::Line),
+            (candidates::format_line(&puzzle), Format::Candidates),
+            (candidates::format_grid(&puzzle), Format::Grid),
+        ];
+        for (body, format) in bodies.iter() {
+            let envelope = parse(body).unwrap();
+            assert_eq!(envelope.format, *format);
+            assert_eq!(envelope.version, VERSION);
+            assert_eq!(envelope.board.to_line(), line);
+        }
+        // the pencilmarks survive the forms that have them
+        for body in [&bodies[0].0, &bodies[1].0, &bodies[5].0] {
+            assert_eq!(
+                parse(body).unwrap().board.pencilmarks(0),
+                puzzle.pencilmarks(0)
+            );
+        }
+        assert!(parse("{\"rows\": []}").is_err());
+        assert!(parse("[{\"contents\": 1, \"pencilmarks\": []}]").is_err());
+    }
+
+    #[test]
+    fn test_envelope() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let squares: Value = serde_json::to_value(&puzzle).unwrap();
+        let wrapped = json!({
+            "version": 1,
+            "format": "wrapped",
+            "board": { "squares": squares },
+            "metadata": { "client": "web", "title": "NYT easy" },
+        });
+        let envelope = parse(&wrapped.to_string()).unwrap();
+        assert_eq!(envelope.format, Format::Wrapped);
+        assert_eq!(envelope.board.to_line(), puzzle.to_line());
+        assert_eq!(envelope.metadata["client"], json!("web"));
+
+        // the format tag can be left out, but one that's given must match
+        let untagged = json!({ "board": puzzle.to_line() });
+        assert_eq!(parse(&untagged.to_string()).unwrap().format, Format::Line);
+        let mistagged = json!({ "format": "squares", "board": puzzle.to_line() });
+        assert!(parse(&mistagged.to_string()).is_err());
+        assert_eq!(
+            parse(r#"{"format":"wrapped","board":{}}"#).err().unwrap(),
+            "reading wrapped board: a wrapped board needs squares"
+        );
+
+        let future = json!({ "version": 2, "board": puzzle.to_line() });
+        assert_eq!(
+            parse(&future.to_string()).err().unwrap(),
+            "unsupported envelope version: 2"
+        );
+        let mut bad_digit = squares.clone();
+        bad_digit[3]["contents"] = json!(10);
+        let bad_digit = json!({ "board": bad_digit });
+        assert_eq!(
+            parse(&bad_digit.to_string()).err().unwrap(),
+            "reading squares board: not a digit in square 3: 10"
+        );
+    }
+}
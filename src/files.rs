@@ -0,0 +1,430 @@
+use crate::board::Board;
+use std::collections::HashSet;
+use std::path::Path;
+
+// Readers and writers for the puzzle files older desktop programs saved.
+//
+// SadMan Sudoku `.sdk` files start with `#` header lines (`#A` author, `#D` description,
+// `#C` comment, `#B` date, `#S` source, `#L` level, `#U` URL), then the givens as nine lines of
+// nine characters with `.` for a blank. A game in progress puts the givens under `[Puzzle]`
+// and the current digits under `[State]` in the same layout.
+//
+// SadMan `.sdx` files take the same headers, then nine lines of nine space-separated cells. A
+// bare digit is a given, `u` and a digit is a digit the player placed, and any other group of
+// digits, with or without the `u`, is an empty cell's candidates. The format can't tell a lone
+// candidate from a placed digit, so an empty cell down to one candidate is written as placed,
+// the way SadMan writes it, and reads back that way.
+//
+// Simple Sudoku `.ss` files are the givens drawn as a grid, `.` for a blank, with `|` between
+// stacks and a line of dashes between bands. They have no headers.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Sdk,
+    Sdx,
+    Ss,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        return extension.parse();
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Format, String> {
+        return match s {
+            "sdk" => Ok(Format::Sdk),
+            "sdx" => Ok(Format::Sdx),
+            "ss" => Ok(Format::Ss),
+            _ => Err(format!("unknown puzzle file format: {}", s)),
+        };
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub comment: Option<String>,
+    pub date: Option<String>,
+    pub source: Option<String>,
+    pub level: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Metadata {
+    fn fields(&mut self) -> [(char, &mut Option<String>); 7] {
+        return [
+            ('A', &mut self.author),
+            ('D', &mut self.description),
+            ('C', &mut self.comment),
+            ('B', &mut self.date),
+            ('S', &mut self.source),
+            ('L', &mut self.level),
+            ('U', &mut self.url),
+        ];
+    }
+    // Reads one `#` line. Unknown tags are skipped rather than refused, since every program
+    // that wrote these files added its own.
+    fn read_header(&mut self, line: &str) {
+        let mut chars = line.trim_start_matches('#').chars();
+        let tag = match chars.next() {
+            Some(tag) => tag.to_ascii_uppercase(),
+            None => return,
+        };
+        let value = chars.as_str().trim().to_string();
+        for (field_tag, field) in self.fields().iter_mut() {
+            if *field_tag == tag {
+                **field = Some(value);
+                return;
+            }
+        }
+    }
+    fn write_headers(&self, out: &mut String) {
+        let mut metadata = self.clone();
+        for (tag, field) in metadata.fields().iter() {
+            if let Some(value) = field {
+                out.push_str(&format!("#{}{}\n", tag, value));
+            }
+        }
+    }
+}
+
+pub struct PuzzleFile {
+    pub metadata: Metadata,
+    pub puzzle: Board,
+    // The player's position, givens included, when the file saved a game in progress.
+    pub state: Option<Board>,
+}
+
+fn parse_cell(ch: char) -> Result<Option<u8>, String> {
+    return match ch {
+        '1'..='9' => Ok(Some(ch as u8 - b'0')),
+        '.' | '0' | 'X' | 'x' | '*' => Ok(None),
+        _ => Err(format!("unexpected character in puzzle: {}", ch)),
+    };
+}
+
+// Nine rows of nine cells, skipping anything drawn between them.
+fn parse_rows(lines: &[&str]) -> Result<Board, String> {
+    let mut digits = [None; 81];
+    let mut count = 0;
+    for line in lines {
+        for ch in line.chars() {
+            if ch.is_whitespace() || ch == '|' || ch == '-' || ch == '+' {
+                continue;
+            }
+            let digit = parse_cell(ch)?;
+            if count < 81 {
+                digits[count] = digit;
+            }
+            count += 1;
+        }
+    }
+    if count != 81 {
+        return Err(format!("puzzle has {} cells, expected 81", count));
+    }
+    return Ok(Board::from_digits(digits));
+}
+
+fn write_rows(board: &Board, out: &mut String) {
+    for cells in board.to_line().as_bytes().chunks(9) {
+        out.push_str(std::str::from_utf8(cells).unwrap());
+        out.push('\n');
+    }
+}
+
+fn read_sdk(text: &str) -> Result<PuzzleFile, String> {
+    let mut metadata = Metadata::default();
+    let mut puzzle_lines = Vec::<&str>::new();
+    let mut state_lines = Vec::<&str>::new();
+    let mut in_state = false;
+    for line in text.lines().map(|line| line.trim()) {
+        if line.starts_with('#') {
+            metadata.read_header(line);
+        } else if line.eq_ignore_ascii_case("[puzzle]") {
+            in_state = false;
+        } else if line.eq_ignore_ascii_case("[state]") {
+            in_state = true;
+        } else if !line.is_empty() {
+            if in_state {
+                state_lines.push(line);
+            } else {
+                puzzle_lines.push(line);
+            }
+        }
+    }
+    let state = if state_lines.is_empty() {
+        None
+    } else {
+        Some(parse_rows(&state_lines)?)
+    };
+    return Ok(PuzzleFile {
+        metadata,
+        puzzle: parse_rows(&puzzle_lines)?,
+        state,
+    });
+}
+
+fn write_sdk(file: &PuzzleFile) -> String {
+    let mut out = String::new();
+    file.metadata.write_headers(&mut out);
+    match &file.state {
+        Some(state) => {
+            out.push_str("[Puzzle]\n");
+            write_rows(&file.puzzle, &mut out);
+            out.push_str("[State]\n");
+            write_rows(state, &mut out);
+        }
+        None => write_rows(&file.puzzle, &mut out),
+    }
+    return out;
+}
+
+fn read_sdx(text: &str) -> Result<PuzzleFile, String> {
+    let mut metadata = Metadata::default();
+    let mut givens = [None; 81];
+    let mut placed = [None; 81];
+    let mut candidates = Vec::<Option<HashSet<u8>>>::with_capacity(81);
+    let mut in_progress = false;
+    for line in text.lines().map(|line| line.trim()) {
+        if line.starts_with('#') {
+            metadata.read_header(line);
+            continue;
+        }
+        for token in line.split_whitespace() {
+            let idx = candidates.len();
+            if idx == 81 {
+                return Err("puzzle has more than 81 cells".to_string());
+            }
+            let user = token.starts_with('u') || token.starts_with('U');
+            let mut digits = HashSet::<u8>::new();
+            for ch in token.trim_start_matches(['u', 'U']).chars() {
+                match parse_cell(ch)? {
+                    Some(digit) => digits.insert(digit),
+                    None => continue,
+                };
+            }
+            let single = if digits.len() == 1 {
+                digits.iter().next().cloned()
+            } else {
+                None
+            };
+            match (user, single) {
+                (false, Some(digit)) => {
+                    givens[idx] = Some(digit);
+                    placed[idx] = Some(digit);
+                    candidates.push(None);
+                }
+                (true, Some(digit)) => {
+                    placed[idx] = Some(digit);
+                    in_progress = true;
+                    candidates.push(None);
+                }
+                _ => {
+                    in_progress = in_progress || digits.len() < 9;
+                    candidates.push(Some(digits));
+                }
+            }
+        }
+    }
+    if candidates.len() != 81 {
+        return Err(format!(
+            "puzzle has {} cells, expected 81",
+            candidates.len()
+        ));
+    }
+    let state = if in_progress {
+        let mut state = Board::from_digits(placed);
+        for (idx, cell) in candidates.into_iter().enumerate() {
+            if let Some(cell) = cell {
+                state.set_pencilmarks(idx, cell);
+            }
+        }
+        Some(state)
+    } else {
+        None
+    };
+    return Ok(PuzzleFile {
+        metadata,
+        puzzle: Board::from_digits(givens),
+        state,
+    });
+}
+
+fn write_sdx(file: &PuzzleFile) -> String {
+    let mut out = String::new();
+    file.metadata.write_headers(&mut out);
+    let position = file.state.as_ref().unwrap_or(&file.puzzle);
+    for row in 0..9 {
+        let mut cells = Vec::<String>::with_capacity(9);
+        for col in 0..9 {
+            let idx = 9 * row + col;
+            let cell = match (file.puzzle.digit(idx), position.digit(idx)) {
+                (Some(given), _) => given.to_string(),
+                (None, Some(digit)) => format!("u{}", digit),
+                (None, None) => {
+                    let mut marks: Vec<u8> = position.pencilmarks(idx).iter().cloned().collect();
+                    marks.sort_unstable();
+                    let marks: String = marks.iter().map(|digit| digit.to_string()).collect();
+                    format!("u{}", marks)
+                }
+            };
+            cells.push(cell);
+        }
+        out.push_str(&cells.join(" "));
+        out.push('\n');
+    }
+    return out;
+}
+
+fn read_ss(text: &str) -> Result<PuzzleFile, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    return Ok(PuzzleFile {
+        metadata: Metadata::default(),
+        puzzle: parse_rows(&lines)?,
+        state: None,
+    });
+}
+
+fn write_ss(file: &PuzzleFile) -> String {
+    let mut out = String::new();
+    let line = file.puzzle.to_line();
+    for (row, cells) in line.as_bytes().chunks(9).enumerate() {
+        if row == 3 || row == 6 {
+            out.push_str("-----------\n");
+        }
+        let cells = std::str::from_utf8(cells).unwrap();
+        out.push_str(&format!(
+            "{}|{}|{}\n",
+            &cells[..3],
+            &cells[3..6],
+            &cells[6..]
+        ));
+    }
+    return out;
+}
+
+pub fn read(text: &str, format: Format) -> Result<PuzzleFile, String> {
+    return match format {
+        Format::Sdk => read_sdk(text),
+        Format::Sdx => read_sdx(text),
+        Format::Ss => read_ss(text),
+    };
+}
+
+// Writes what the format can hold: `.ss` drops the metadata and any game in progress, and
+// `.sdk` drops the candidates.
+pub fn write(file: &PuzzleFile, format: Format) -> String {
+    return match format {
+        Format::Sdk => write_sdk(file),
+        Format::Sdx => write_sdx(file),
+        Format::Ss => write_ss(file),
+    };
+}
+
+// Reads a file, going by its extension for the format.
+pub fn read_path(path: &Path) -> Result<PuzzleFile, String> {
+    let format = Format::from_path(path)?;
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+    return read(&text, format);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASY: &str =
+        "48.1..6.9..7.8.1.55.6.472....8..1...6.28735..7...6.9.3.746.8.9.86.7.2....3.....67";
+
+    fn easy_file() -> PuzzleFile {
+        return PuzzleFile {
+            metadata: Metadata {
+                author: Some("NYT".to_string()),
+                level: Some("Easy".to_string()),
+                ..Metadata::default()
+            },
+            puzzle: EASY.parse().unwrap(),
+            state: None,
+        };
+    }
+
+    #[test]
+    fn test_sdk() {
+        let text = "#AThe Times\n#D A quick one\n#Xsomething else\n48.1..6.9\n..7.8.1.5\n\
+                    5.6.472..\n..8..1...\n6.28735..\n7...6.9.3\n.746.8.9.\n86.7.2...\n.3.....67\n";
+        let file = read(text, Format::Sdk).unwrap();
+        assert_eq!(file.metadata.author, Some("The Times".to_string()));
+        assert_eq!(file.metadata.description, Some("A quick one".to_string()));
+        assert_eq!(file.puzzle.to_line(), EASY);
+        assert!(file.state.is_none());
+
+        // the player has put a 3 in cell 2
+        let mut saved = easy_file();
+        saved.state = Some(format!("{}3{}", &EASY[..2], &EASY[3..]).parse().unwrap());
+        let reread = read(&write(&saved, Format::Sdk), Format::Sdk).unwrap();
+        assert_eq!(reread.metadata, saved.metadata);
+        assert_eq!(reread.puzzle.to_line(), EASY);
+        assert_eq!(reread.state.unwrap().digit(2), Some(3));
+    }
+
+    #[test]
+    fn test_sdx() {
+        let mut file = easy_file();
+        let mut state: Board = EASY.parse().unwrap();
+        state.apply_pencilmark_policy(crate::board::PencilmarkPolicy::Recompute);
+        let mut marks = state.pencilmarks(4).clone();
+        marks.insert(9);
+        state.set_pencilmarks(4, marks.clone());
+        file.state = Some(state);
+
+        let written = write(&file, Format::Sdx);
+        assert!(written.starts_with("#ANYT\n#LEasy\n4 8 u"));
+        let reread = read(&written, Format::Sdx).unwrap();
+        assert_eq!(reread.metadata, file.metadata);
+        assert_eq!(reread.puzzle.to_line(), EASY);
+        assert_eq!(reread.state.unwrap().pencilmarks(4), &marks);
+
+        // an empty cell down to one candidate comes back placed
+        let mut state = file.state.clone().unwrap();
+        state.set_pencilmarks(4, [7].iter().cloned().collect());
+        file.state = Some(state);
+        let written_single = write(&file, Format::Sdx);
+        assert!(written_single.contains(" u7 "));
+        let reread = read(&written_single, Format::Sdx).unwrap().state.unwrap();
+        assert_eq!(reread.digit(4), Some(7));
+
+        // the player has put a 3 in cell 2
+        let mut cells: Vec<&str> = written
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| line.split_whitespace())
+            .collect();
+        cells[2] = "u3";
+        let reread = read(&cells.join(" "), Format::Sdx).unwrap();
+        assert_eq!(reread.puzzle.digit(2), None);
+        assert_eq!(reread.state.unwrap().digit(2), Some(3));
+        assert!(read("1 2 3", Format::Sdx).is_err());
+    }
+
+    #[test]
+    fn test_ss() {
+        let written = write(&easy_file(), Format::Ss);
+        assert!(written.starts_with("48.|1..|6.9\n"));
+        assert_eq!(written.lines().count(), 11);
+        assert_eq!(read(&written, Format::Ss).unwrap().puzzle.to_line(), EASY);
+        assert!(read("12.|...", Format::Ss).is_err());
+        assert_eq!(
+            Format::from_path(Path::new("old/Puzzle 1.SDX")),
+            Ok(Format::Sdx)
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::board::Board;
+use crate::lzstring;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+// Puzzles shared as f-puzzles links, which SudokuPad opens too. The link carries the puzzle as
+// JSON, lz-string compressed to base64:
+//
+//     https://www.f-puzzles.com/?load=N4IgzglgXgpiBcB...
+//     https://sudokupad.app/fpuzzlesN4IgzglgXgpiBcB...
+//
+// The JSON has the size, title and author, and a 9x9 `grid` of cells. A cell can have a
+// `value`, marked `given` if it's a clue, and `centerPencilMarks` for candidates. Anything
+// else at the top level is a variant constraint. This server only plays classic sudoku, so a
+// puzzle with a constraint, or with irregular regions, is refused rather than served without
+// its extra rules. Cosmetic fields such as cell colours are dropped.
+
+pub struct Shared {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub puzzle: Board,
+    // The player's position, givens included, when the link saved digits or candidates.
+    pub state: Option<Board>,
+}
+
+// Top-level fields that don't change the rules.
+const PLAIN_FIELDS: [&str; 7] = [
+    "size",
+    "title",
+    "author",
+    "ruleset",
+    "grid",
+    "solution",
+    "disabledlogic",
+];
+
+fn is_set(value: &Value) -> bool {
+    return match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::Array(items) => !items.is_empty(),
+        Value::String(text) => !text.is_empty(),
+        _ => true,
+    };
+}
+
+fn marks(cell: &Value, field: &str) -> Result<HashSet<u8>, String> {
+    let mut marks = HashSet::<u8>::new();
+    for mark in cell[field]
+        .as_array()
+        .map(|marks| marks.as_slice())
+        .unwrap_or(&[])
+    {
+        match mark.as_u64() {
+            Some(digit @ 1..=9) => marks.insert(digit as u8),
+            _ => return Err(format!("not a candidate: {}", mark)),
+        };
+    }
+    return Ok(marks);
+}
+
+pub fn from_json(text: &str) -> Result<Shared, String> {
+    let puzzle: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    let fields = puzzle.as_object().ok_or("puzzle isn't a JSON object")?;
+    if puzzle["size"].as_u64() != Some(9) {
+        return Err(format!(
+            "only 9x9 puzzles are supported, not size {}",
+            puzzle["size"]
+        ));
+    }
+    for (name, value) in fields {
+        if !PLAIN_FIELDS.contains(&name.as_str()) && is_set(value) {
+            return Err(format!("unsupported constraint: {}", name));
+        }
+    }
+
+    let rows = puzzle["grid"].as_array().ok_or("puzzle has no grid")?;
+    if rows.len() != 9 {
+        return Err(format!("grid has {} rows, expected 9", rows.len()));
+    }
+    let cells: Vec<&Value> = rows
+        .iter()
+        .map(|row| match row.as_array() {
+            Some(row) if row.len() == 9 => Ok(row),
+            _ => Err("every grid row needs 9 cells".to_string()),
+        })
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut givens = [None; 81];
+    let mut placed = [None; 81];
+    let mut candidates = vec![HashSet::<u8>::new(); 81];
+    let mut in_progress = false;
+    for (idx, cell) in cells.iter().enumerate() {
+        if is_set(&cell["region"]) {
+            return Err("unsupported constraint: irregular regions".to_string());
+        }
+        match cell["value"].as_u64() {
+            Some(digit @ 1..=9) => {
+                placed[idx] = Some(digit as u8);
+                if cell["given"].as_bool() == Some(true) {
+                    givens[idx] = Some(digit as u8);
+                } else {
+                    in_progress = true;
+                }
+            }
+            Some(_) => return Err(format!("not a digit: {}", cell["value"])),
+            None => {
+                candidates[idx] = marks(cell, "centerPencilMarks")?;
+                in_progress = in_progress || !candidates[idx].is_empty();
+            }
+        }
+    }
+    let state = if in_progress {
+        let mut state = Board::from_digits(placed);
+        for (idx, cell) in candidates.into_iter().enumerate() {
+            if placed[idx].is_none() && !cell.is_empty() {
+                state.set_pencilmarks(idx, cell);
+            }
+        }
+        Some(state)
+    } else {
+        None
+    };
+    let text_field = |name: &str| puzzle[name].as_str().map(|text| text.to_string());
+    return Ok(Shared {
+        title: text_field("title"),
+        author: text_field("author"),
+        puzzle: Board::from_digits(givens),
+        state,
+    });
+}
+
+pub fn to_json(shared: &Shared) -> String {
+    let position = shared.state.as_ref().unwrap_or(&shared.puzzle);
+    let mut grid = Vec::<Value>::with_capacity(9);
+    for row in 0..9 {
+        let mut cells = Vec::<Value>::with_capacity(9);
+        for col in 0..9 {
+            let idx = 9 * row + col;
+            let mut cell = Map::new();
+            match (shared.puzzle.digit(idx), position.digit(idx)) {
+                (Some(given), _) => {
+                    cell.insert("value".to_string(), json!(given));
+                    cell.insert("given".to_string(), json!(true));
+                }
+                (None, Some(digit)) => {
+                    cell.insert("value".to_string(), json!(digit));
+                }
+                // Every candidate still open is the same as no notes, so only narrowed cells
+                // are written.
+                (None, None) if shared.state.is_some() => {
+                    let mut marks: Vec<u8> = position.pencilmarks(idx).iter().cloned().collect();
+                    marks.sort_unstable();
+                    if !marks.is_empty() && marks.len() < 9 {
+                        cell.insert("centerPencilMarks".to_string(), json!(marks));
+                    }
+                }
+                (None, None) => {}
+            }
+            cells.push(Value::Object(cell));
+        }
+        grid.push(Value::Array(cells));
+    }
+    let mut puzzle = json!({ "size": 9, "grid": grid });
+    if let Some(title) = &shared.title {
+        puzzle["title"] = json!(title);
+    }
+    if let Some(author) = &shared.author {
+        puzzle["author"] = json!(author);
+    }
+    return puzzle.to_string();
+}
+
+// Undoes the percent-encoding base64 can pick up in a query string.
+fn unescape(payload: &str) -> String {
+    return payload
+        .replace("%2B", "+")
+        .replace("%2b", "+")
+        .replace("%2F", "/")
+        .replace("%2f", "/")
+        .replace("%3D", "=")
+        .replace("%3d", "=")
+        .replace(' ', "+");
+}
+
+// Takes an f-puzzles or SudokuPad link, the compressed text on its own, or the JSON itself.
+pub fn decode(text: &str) -> Result<Shared, String> {
+    let text = text.trim();
+    if text.starts_with('{') {
+        return from_json(text);
+    }
+    let payload = if let Some((_, query)) = text.split_once("load=") {
+        query
+    } else if let Some((_, rest)) = text.rsplit_once("fpuzzles") {
+        rest
+    } else if text.contains("://") {
+        return Err("not an f-puzzles or SudokuPad link".to_string());
+    } else {
+        text
+    };
+    let payload = payload.split(['&', '#']).next().unwrap_or("");
+    return from_json(&lzstring::decompress_from_base64(&unescape(payload))?);
+}
+
+pub fn encode(shared: &Shared) -> String {
+    return lzstring::compress_to_base64(&to_json(shared));
+}
+
+pub fn fpuzzles_url(shared: &Shared) -> String {
+    return format!("https://www.f-puzzles.com/?load={}", encode(shared));
+}
+
+pub fn sudokupad_url(shared: &Shared) -> String {
+    return format!("https://sudokupad.app/fpuzzles{}", encode(shared));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let puzzle = crate::catalog::test_board("nyt-hard");
+        let mut line = puzzle.to_line();
+        // the player has filled in cell 13, a naked single, and noted two candidates in cell 0
+        line.replace_range(13..14, "3");
+        let mut state: Board = line.parse().unwrap();
+        state.set_pencilmarks(0, [4, 7].iter().cloned().collect());
+        let shared = Shared {
+            title: Some("NYT hard".to_string()),
+            author: None,
+            puzzle: puzzle.clone(),
+            state: Some(state.clone()),
+        };
+
+        for url in [fpuzzles_url(&shared), sudokupad_url(&shared)] {
+            let decoded = decode(&url).unwrap();
+            assert_eq!(decoded.title.as_deref(), Some("NYT hard"));
+            assert_eq!(decoded.puzzle.to_line(), puzzle.to_line());
+            let decoded_state = decoded.state.unwrap();
+            assert_eq!(decoded_state.to_line(), state.to_line());
+            assert_eq!(decoded_state.pencilmarks(0), state.pencilmarks(0));
+        }
+        let query = format!("https://sudokupad.app/?puzzle=fpuzzles{}", encode(&shared));
+        assert!(decode(&query.replace('+', "%2B")).is_ok());
+    }
+
+    #[test]
+    fn test_constraints() {
+        let grid = vec![vec![json!({}); 9]; 9];
+        let plain = json!({ "size": 9, "grid": grid, "title": "Blank", "diagonal+": false });
+        let decoded = decode(&lzstring::compress_to_base64(&plain.to_string())).unwrap();
+        assert_eq!(decoded.title.as_deref(), Some("Blank"));
+        assert!(decoded.state.is_none());
+
+        let diagonal = json!({ "size": 9, "grid": grid, "diagonal+": true });
+        assert_eq!(
+            decode(&diagonal.to_string()).err().unwrap(),
+            "unsupported constraint: diagonal+"
+        );
+        let killer = json!({ "size": 9, "grid": grid, "killercage": [{ "cells": ["R1C1"] }] });
+        assert!(decode(&killer.to_string()).is_err());
+        let six = json!({ "size": 6, "grid": grid });
+        assert!(decode(&six.to_string()).is_err());
+        // 81 cells, but not nine to a row
+        let mut ragged = grid.clone();
+        ragged[0].push(json!({}));
+        ragged[1].pop();
+        let ragged = json!({ "size": 9, "grid": ragged });
+        assert_eq!(
+            decode(&ragged.to_string()).err().unwrap(),
+            "every grid row needs 9 cells"
+        );
+    }
+}
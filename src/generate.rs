@@ -0,0 +1,499 @@
+use crate::board::Board;
+use crate::budget::{Budget, Meter, StopReason};
+use crate::grade::{self, Difficulty, Grade, Technique};
+use crate::rng::Rng;
+use crate::search;
+
+// Builds puzzles by filling a random grid and taking clues away one at a time, keeping each
+// removal only if the solution stays unique and the grade doesn't go past the target. A fixed
+// clue mask is worked the other way round, with the clues' digits changed until they fit.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symmetry {
+    None,
+    // Half-turn rotation about the centre cell.
+    Rotational,
+    // Reflection in the main diagonal.
+    Diagonal,
+    // Reflection left to right.
+    Mirror,
+}
+
+impl Symmetry {
+    // The cells that have to be given or blank together with `idx`.
+    fn orbit(self, idx: usize) -> Vec<usize> {
+        let (row, col) = (idx / 9, idx % 9);
+        let image = match self {
+            Symmetry::None => idx,
+            Symmetry::Rotational => 80 - idx,
+            Symmetry::Diagonal => 9 * col + row,
+            Symmetry::Mirror => 9 * row + (8 - col),
+        };
+        if image == idx {
+            return vec![idx];
+        }
+        return vec![idx, image];
+    }
+}
+
+impl std::str::FromStr for Symmetry {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Symmetry, String> {
+        return match s {
+            "none" => Ok(Symmetry::None),
+            "rotational" => Ok(Symmetry::Rotational),
+            "diagonal" => Ok(Symmetry::Diagonal),
+            "mirror" => Ok(Symmetry::Mirror),
+            _ => Err(format!("unknown symmetry: {}", s)),
+        };
+    }
+}
+
+// Where the clues are allowed to go.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Layout {
+    Symmetric(Symmetry),
+    // Exactly these cells are given, nothing more or less. The clue digits are fitted to the
+    // mask, which finds unique puzzles for masks of the usual 22 clues or more.
+    Mask([bool; 81]),
+}
+
+// No 9x9 sudoku with fewer clues than this has a unique solution.
+pub const MIN_CLUES: usize = 17;
+
+// Reads a clue mask drawn as 81 cells, where `x`, `X`, `#`, `*` or `1` mark a clue and `.`, `-`
+// or `0` a blank. Whitespace is ignored so the mask can be laid out as a grid.
+pub fn parse_mask(s: &str) -> Result<[bool; 81], String> {
+    let mut mask = [false; 81];
+    let mut count = 0;
+    for ch in s.chars().filter(|ch| !ch.is_whitespace()) {
+        let clue = match ch {
+            'x' | 'X' | '#' | '*' | '1' => true,
+            '.' | '-' | '0' => false,
+            _ => return Err(format!("unexpected character in clue mask: {}", ch)),
+        };
+        if count < 81 {
+            mask[count] = clue;
+        }
+        count += 1;
+    }
+    if count != 81 {
+        return Err(format!("clue mask has {} cells, expected 81", count));
+    }
+    return Ok(mask);
+}
+
+pub struct GenerateOptions {
+    pub difficulty: Difficulty,
+    pub layout: Layout,
+    // All randomness comes from this, so the same seed and options always give the same puzzle.
+    pub seed: u64,
+    // A technique the solve path has to use.
+    pub require: Option<Technique>,
+    // The hardest technique the solve path may use.
+    pub max_technique: Option<Technique>,
+    // How many full grids to try before giving up on hitting the difficulty.
+    pub max_attempts: usize,
+}
+
+impl GenerateOptions {
+    pub fn new(difficulty: Difficulty) -> GenerateOptions {
+        return GenerateOptions {
+            difficulty,
+            layout: Layout::Symmetric(Symmetry::None),
+            seed: 0,
+            require: None,
+            max_technique: None,
+            max_attempts: 100,
+        };
+    }
+    // Puzzles for practicing one technique: they need it, and nothing harder. The harder
+    // techniques turn up in well under one random grid in a hundred, hence the extra attempts.
+    pub fn practicing(technique: Technique) -> GenerateOptions {
+        let mut options = GenerateOptions::new(technique.difficulty());
+        options.require = Some(technique);
+        options.max_technique = Some(technique);
+        options.max_attempts = 1000;
+        return options;
+    }
+    pub fn with_seed(mut self, seed: u64) -> GenerateOptions {
+        self.seed = seed;
+        return self;
+    }
+    pub fn with_max_technique(mut self, technique: Technique) -> GenerateOptions {
+        self.max_technique = Some(technique);
+        return self;
+    }
+    // Whether a puzzle with this grade may be worked towards, i.e. isn't already too hard.
+    fn allows(&self, grade: &Grade) -> bool {
+        let within_cap = match self.max_technique {
+            Some(cap) => grade.hardest <= cap,
+            None => true,
+        };
+        return within_cap && grade.difficulty <= self.difficulty;
+    }
+    // Whether a puzzle with this grade is what was asked for.
+    fn hits(&self, grade: &Grade) -> bool {
+        let required = match self.require {
+            Some(technique) => grade.uses(technique),
+            None => true,
+        };
+        return required && self.allows(grade) && grade.difficulty == self.difficulty;
+    }
+    pub fn with_layout(mut self, layout: Layout) -> GenerateOptions {
+        self.layout = layout;
+        return self;
+    }
+}
+
+#[derive(Clone)]
+pub struct Generated {
+    pub puzzle: Board,
+    pub solution: Board,
+    pub grade: Grade,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GenerateError {
+    // No attempt landed in the requested band.
+    Exhausted,
+    // The mask has too few clues for any puzzle on it to be unique.
+    TooFewClues(usize),
+    // The mask can't give a unique puzzle, or fitting clues to it kept failing.
+    MaskNotUnique,
+    // The budget ran out before an attempt hit.
+    Unfinished(StopReason),
+}
+
+impl std::fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GenerateError::Exhausted => {
+                write!(f, "ran out of attempts at the requested difficulty")
+            }
+            GenerateError::TooFewClues(clues) => write!(
+                f,
+                "the clue mask has {} clues, and a unique puzzle needs at least {}",
+                clues, MIN_CLUES
+            ),
+            GenerateError::MaskNotUnique => write!(
+                f,
+                "couldn't find a unique puzzle on the clue mask; try one with more clues"
+            ),
+            GenerateError::Unfinished(reason) => write!(f, "gave up generating: {}", reason),
+        }
+    }
+}
+
+fn to_board(digits: &[Option<u8>; 81]) -> Board {
+    return Board::from_digits(*digits);
+}
+
+// Strip clues from `solution` in a random order, as far as the options allow. Cells related by
+// the symmetry are removed together so the layout keeps its shape. Taking more clues away can
+// also stop a required technique being needed, so the last puzzle that hit the target is
+// returned if the final one doesn't.
+fn remove_clues(
+    solution: &Board,
+    options: &GenerateOptions,
+    symmetry: Symmetry,
+    rng: &mut Rng,
+) -> (Board, Grade) {
+    let mut digits = [None; 81];
+    for (idx, digit) in digits.iter_mut().enumerate() {
+        *digit = solution.digit(idx);
+    }
+    let mut order: Vec<usize> = (0..81).collect();
+    rng.shuffle(&mut order);
+    let mut current = grade::grade(&to_board(&digits));
+    let mut last_hit: Option<(Board, Grade)> = None;
+    for idx in order {
+        if digits[idx].is_none() {
+            continue;
+        }
+        let orbit = symmetry.orbit(idx);
+        let removed: Vec<Option<u8>> = orbit.iter().map(|cell| digits[*cell].take()).collect();
+        let candidate = to_board(&digits);
+        if search::count_solutions(&candidate, 2) == 1 {
+            let candidate_grade = grade::grade(&candidate);
+            if options.allows(&candidate_grade) {
+                if options.hits(&candidate_grade) {
+                    last_hit = Some((candidate, candidate_grade.clone()));
+                }
+                current = candidate_grade;
+                continue;
+            }
+        }
+        for (cell, digit) in orbit.iter().zip(removed) {
+            digits[*cell] = digit;
+        }
+    }
+    if !options.hits(&current) {
+        if let Some(hit) = last_hit {
+            return hit;
+        }
+    }
+    return (to_board(&digits), current);
+}
+
+// Completions are counted up to this many while fitting a mask; past it, every puzzle looks as
+// ambiguous as the next.
+const FIT_COUNT_LIMIT: usize = 200;
+// How many clue changes one fit tries before giving up on the grid it started from.
+const MAX_FIT_CHANGES: usize = 3000;
+// How many fits in a row may fail before the mask is given up on.
+const MAX_FAILED_FITS: usize = 8;
+// Proving a sparse puzzle has no completions can take a long search, so each count gets this
+// many nodes, and a change whose count doesn't finish is treated as a bad one.
+const FIT_MAX_NODES: u64 = 20_000;
+
+// Two blank rows in the same band can swap digits in any solution and leave another, and the
+// same goes for columns in a stack, so such a mask never has a unique puzzle.
+fn mask_allows_unique(mask: &[bool; 81]) -> bool {
+    let blank_rows: Vec<usize> = (0..9)
+        .filter(|row| (0..9).all(|col| !mask[9 * row + col]))
+        .collect();
+    let blank_cols: Vec<usize> = (0..9)
+        .filter(|col| (0..9).all(|row| !mask[9 * row + col]))
+        .collect();
+    let shared_third = |lines: &[usize]| {
+        lines.iter().any(|line| {
+            lines
+                .iter()
+                .any(|other| other != line && other / 3 == line / 3)
+        })
+    };
+    return !shared_third(&blank_rows) && !shared_third(&blank_cols);
+}
+
+fn sees(a: usize, b: usize) -> bool {
+    return a / 9 == b / 9 || a % 9 == b % 9 || (a / 27 == b / 27 && (a % 9) / 3 == (b % 9) / 3);
+}
+
+// Digits for exactly the masked cells that give a unique puzzle. Few grids are unique on a given
+// mask, so rather than hoping for one this starts from `start`'s digits on the masked cells and
+// changes one clue at a time, keeping each change that leaves the puzzle solvable with no more
+// completions than before, until only one is left. Gives the puzzle and its solution, or None if
+// it runs out of changes first.
+fn fit_mask(start: &Board, mask: &[bool; 81], rng: &mut Rng) -> Option<(Board, Board)> {
+    let cells: Vec<usize> = (0..81).filter(|idx| mask[*idx]).collect();
+    let mut digits = [None; 81];
+    for idx in cells.iter() {
+        digits[*idx] = start.digit(*idx);
+    }
+    let budget = Budget::unlimited().with_max_nodes(FIT_MAX_NODES);
+    let count_within = |digits: &[Option<u8>; 81]| {
+        let outcome = search::count_solutions_within(&to_board(digits), FIT_COUNT_LIMIT, &budget);
+        return outcome.is_complete().then_some(outcome.result);
+    };
+    // The start is a grid's own digits, so it has at least one completion.
+    let mut count = count_within(&digits).unwrap_or(FIT_COUNT_LIMIT);
+    for _ in 0..MAX_FIT_CHANGES {
+        if count == 1 {
+            break;
+        }
+        let idx = cells[rng.below(cells.len())];
+        let digit = rng.below(9) as u8 + 1;
+        let clashes = cells
+            .iter()
+            .any(|other| *other != idx && sees(idx, *other) && digits[*other] == Some(digit));
+        if clashes || digits[idx] == Some(digit) {
+            continue;
+        }
+        let previous = digits[idx].replace(digit);
+        match count_within(&digits) {
+            Some(changed) if changed >= 1 && changed <= count => count = changed,
+            _ => digits[idx] = previous,
+        }
+    }
+    if count != 1 {
+        return None;
+    }
+    let puzzle = to_board(&digits);
+    let solution = search::random_solution(&puzzle, rng)?;
+    return Some((puzzle, solution));
+}
+
+pub fn generate(options: &GenerateOptions) -> Result<Generated, GenerateError> {
+    return generate_within(options, &Budget::unlimited());
+}
+
+// `generate` within a budget, each attempt at a full grid costing one step.
+pub fn generate_within(
+    options: &GenerateOptions,
+    budget: &Budget,
+) -> Result<Generated, GenerateError> {
+    return generate_metered(options, &mut Meter::new(budget));
+}
+
+// `generate_within` charging an existing meter, so several generations can share one budget.
+pub fn generate_metered(
+    options: &GenerateOptions,
+    meter: &mut Meter,
+) -> Result<Generated, GenerateError> {
+    if let Layout::Mask(mask) = &options.layout {
+        let clues = mask.iter().filter(|clue| **clue).count();
+        if clues < MIN_CLUES {
+            return Err(GenerateError::TooFewClues(clues));
+        }
+        if !mask_allows_unique(mask) {
+            return Err(GenerateError::MaskNotUnique);
+        }
+    }
+    let rng = &mut Rng::new(options.seed);
+    let mut any_unique = false;
+    let mut failed_fits = 0;
+    for _ in 0..options.max_attempts {
+        if let Some(reason) = meter.step() {
+            return Err(GenerateError::Unfinished(reason));
+        }
+        let solution = match search::random_solution(&Board::from_digits([None; 81]), rng) {
+            Some(solution) => solution,
+            None => continue,
+        };
+        let (puzzle, solution, grade) = match &options.layout {
+            Layout::Symmetric(symmetry) => {
+                let (puzzle, grade) = remove_clues(&solution, options, *symmetry, rng);
+                (puzzle, solution, grade)
+            }
+            Layout::Mask(mask) => match fit_mask(&solution, mask, rng) {
+                Some((puzzle, solution)) => {
+                    let grade = grade::grade(&puzzle);
+                    (puzzle, solution, grade)
+                }
+                None => {
+                    failed_fits += 1;
+                    if failed_fits == MAX_FAILED_FITS {
+                        break;
+                    }
+                    continue;
+                }
+            },
+        };
+        failed_fits = 0;
+        any_unique = true;
+        if options.hits(&grade) {
+            return Ok(Generated {
+                puzzle,
+                solution,
+                grade,
+            });
+        }
+    }
+    if !any_unique {
+        return Err(GenerateError::MaskNotUnique);
+    }
+    return Err(GenerateError::Exhausted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_easy_and_medium() {
+        for difficulty in [Difficulty::Easy, Difficulty::Medium].iter() {
+            let generated = generate(&GenerateOptions::new(*difficulty).with_seed(7)).unwrap();
+            assert_eq!(generated.grade.difficulty, *difficulty);
+            assert_eq!(search::count_solutions(&generated.puzzle, 2), 1);
+            for idx in 0..81 {
+                if let Some(digit) = generated.puzzle.digit(idx) {
+                    assert_eq!(generated.solution.digit(idx), Some(digit));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_puzzle() {
+        let options = GenerateOptions::new(Difficulty::Medium).with_seed(1234);
+        let first = generate(&options).unwrap();
+        let second = generate(&options).unwrap();
+        for idx in 0..81 {
+            assert_eq!(first.puzzle.digit(idx), second.puzzle.digit(idx));
+        }
+        let other = generate(&options.with_seed(4321)).unwrap();
+        assert!((0..81).any(|idx| first.solution.digit(idx) != other.solution.digit(idx)));
+    }
+
+    #[test]
+    fn test_practice_techniques() {
+        for technique in [Technique::LockedCandidates, Technique::NakedPair].iter() {
+            let options = GenerateOptions::practicing(*technique).with_seed(5);
+            let generated = generate(&options).unwrap();
+            assert!(generated.grade.uses(*technique));
+            assert_eq!(generated.grade.hardest, *technique);
+        }
+
+        let capped = GenerateOptions::new(Difficulty::Medium)
+            .with_max_technique(Technique::LockedCandidates)
+            .with_seed(5);
+        let generated = generate(&capped).unwrap();
+        assert_eq!(generated.grade.hardest, Technique::LockedCandidates);
+    }
+
+    #[test]
+    fn test_symmetric_layouts() {
+        let symmetries = [Symmetry::Rotational, Symmetry::Diagonal, Symmetry::Mirror];
+        for symmetry in symmetries.iter() {
+            let options = GenerateOptions::new(Difficulty::Medium)
+                .with_layout(Layout::Symmetric(*symmetry))
+                .with_seed(11);
+            let generated = generate(&options).unwrap();
+            for idx in 0..81 {
+                for image in symmetry.orbit(idx) {
+                    assert_eq!(
+                        generated.puzzle.digit(idx).is_some(),
+                        generated.puzzle.digit(image).is_some()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mask_layout() {
+        // The clue layout of the easy NYT puzzle, which is known to allow a unique solution.
+        let mut mask = [false; 81];
+        let easy = crate::catalog::test_board("nyt-easy");
+        for (idx, clue) in mask.iter_mut().enumerate() {
+            *clue = easy.digit(idx).is_some();
+        }
+        let options = GenerateOptions::new(Difficulty::Easy)
+            .with_layout(Layout::Mask(mask))
+            .with_seed(3);
+        let generated = generate(&options).unwrap();
+        for (idx, clue) in mask.iter().enumerate() {
+            assert_eq!(generated.puzzle.digit(idx).is_some(), *clue);
+        }
+
+        // a sparse pattern of the kind drawn in an editor, which few random grids are unique on
+        let drawn = "x.x...x.x ...x.x... .x.....x. x...x...x ..x...x.. x...x...x .x.....x. ...x.x... x.x...x.x";
+        let sparse = parse_mask(drawn).unwrap();
+        let options = GenerateOptions::new(Difficulty::Medium)
+            .with_layout(Layout::Mask(sparse))
+            .with_seed(3);
+        let generated = generate(&options).unwrap();
+        assert_eq!(search::count_solutions(&generated.puzzle, 2), 1);
+        for (idx, clue) in sparse.iter().enumerate() {
+            assert_eq!(generated.puzzle.digit(idx).is_some(), *clue);
+        }
+
+        // the first two rows and a bit leave the rows below free to swap
+        let mut sparse = [false; 81];
+        sparse[..20].iter_mut().for_each(|clue| *clue = true);
+        let options = GenerateOptions::new(Difficulty::Easy).with_layout(Layout::Mask(sparse));
+        assert_eq!(generate(&options).err(), Some(GenerateError::MaskNotUnique));
+        sparse[16..].iter_mut().for_each(|clue| *clue = false);
+        let options = options.with_layout(Layout::Mask(sparse));
+        assert_eq!(
+            generate(&options).err(),
+            Some(GenerateError::TooFewClues(16))
+        );
+
+        assert!(parse_mask(&"x.".repeat(40)).is_err());
+        let drawn = format!("{}\n{}", "x".repeat(9), ".".repeat(72));
+        let parsed = parse_mask(&drawn).unwrap();
+        assert!(parsed[..9].iter().all(|clue| *clue) && !parsed[9]);
+    }
+}
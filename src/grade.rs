@@ -0,0 +1,515 @@
+use crate::board::Board;
+use serde::{Deserialize, Serialize};
+
+// Rates a puzzle by the hardest technique a person needs to solve it. The grader always applies
+// the easiest technique that makes progress, so the trace reads like a sensible human solve.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    LockedCandidates,
+    NakedPair,
+    HiddenPair,
+    NakedTriple,
+    HiddenTriple,
+    XWing,
+    Swordfish,
+    // Not a technique: the grader got stuck and the puzzle needs guessing or something stronger.
+    Backtracking,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    Fiendish,
+}
+
+impl Technique {
+    pub fn difficulty(self) -> Difficulty {
+        return match self {
+            Technique::NakedSingle | Technique::HiddenSingle => Difficulty::Easy,
+            Technique::LockedCandidates | Technique::NakedPair | Technique::HiddenPair => {
+                Difficulty::Medium
+            }
+            Technique::NakedTriple | Technique::HiddenTriple => Difficulty::Hard,
+            Technique::XWing | Technique::Swordfish => Difficulty::Expert,
+            Technique::Backtracking => Difficulty::Fiendish,
+        };
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        return match s {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "expert" => Ok(Difficulty::Expert),
+            "fiendish" => Ok(Difficulty::Fiendish),
+            _ => Err(format!("unknown difficulty: {}", s)),
+        };
+    }
+}
+
+// One deduction: the technique, the cells whose candidates form the pattern, and what it
+// placed or eliminated.
+#[derive(Clone, Debug, Serialize)]
+pub struct Step {
+    pub technique: Technique,
+    pub pattern: Vec<usize>,
+    pub placements: Vec<(usize, u8)>,
+    pub eliminations: Vec<(usize, u8)>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Grade {
+    pub difficulty: Difficulty,
+    pub hardest: Technique,
+    pub solved: bool,
+    pub steps: Vec<Step>,
+}
+
+impl Grade {
+    pub fn uses(&self, technique: Technique) -> bool {
+        return self.steps.iter().any(|step| step.technique == technique);
+    }
+}
+
+const ALL: u16 = 0b11_1111_1110;
+
+fn bit(digit: u8) -> u16 {
+    return 1 << digit;
+}
+
+fn digits_of(mask: u16) -> impl Iterator<Item = u8> {
+    return (1..10u8).filter(move |digit| mask & bit(*digit) != 0);
+}
+
+// Houses 0-8 are rows, 9-17 columns and 18-26 boxes, with boxes numbered across then down.
+fn houses() -> Vec<[usize; 9]> {
+    let mut houses = Vec::<[usize; 9]>::with_capacity(27);
+    for row in 0..9 {
+        let mut house = [0; 9];
+        for (col, cell) in house.iter_mut().enumerate() {
+            *cell = 9 * row + col;
+        }
+        houses.push(house);
+    }
+    for col in 0..9 {
+        let mut house = [0; 9];
+        for (row, cell) in house.iter_mut().enumerate() {
+            *cell = 9 * row + col;
+        }
+        houses.push(house);
+    }
+    for bx in 0..9 {
+        let mut house = [0; 9];
+        for (i, cell) in house.iter_mut().enumerate() {
+            *cell = 9 * (3 * (bx / 3) + i / 3) + 3 * (bx % 3) + i % 3;
+        }
+        houses.push(house);
+    }
+    return houses;
+}
+
+fn box_of(idx: usize) -> usize {
+    return 3 * (idx / 27) + (idx % 9) / 3;
+}
+
+fn sees(a: usize, b: usize) -> bool {
+    return a != b && (a / 9 == b / 9 || a % 9 == b % 9 || box_of(a) == box_of(b));
+}
+
+// Every way of choosing `n` items from `items`, in order.
+fn combinations(items: &[usize], n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    let mut combos = Vec::<Vec<usize>>::new();
+    for i in 0..items.len() {
+        for mut rest in combinations(&items[i + 1..], n - 1) {
+            rest.insert(0, items[i]);
+            combos.push(rest);
+        }
+    }
+    return combos;
+}
+
+pub struct Grader {
+    cells: [u8; 81],
+    candidates: [u16; 81],
+    houses: Vec<[usize; 9]>,
+}
+
+impl Grader {
+    // Candidates start from the placed digits alone; the board's own pencilmarks are ignored so
+    // a grade never depends on what a player has or hasn't penciled in.
+    pub fn new(board: &Board) -> Grader {
+        let mut grader = Grader {
+            cells: [0; 81],
+            candidates: [ALL; 81],
+            houses: houses(),
+        };
+        for idx in 0..81 {
+            if let Some(digit) = board.digit(idx) {
+                grader.place(idx, digit);
+            }
+        }
+        return grader;
+    }
+    // Starts from the board's pencilmarks as well as its digits, for grading a position
+    // somebody is partway through.
+    pub fn with_pencilmarks(board: &Board) -> Grader {
+        let mut grader = Grader::new(board);
+        for idx in 0..81 {
+            if grader.cells[idx] != 0 {
+                continue;
+            }
+            let mut marks = 0u16;
+            for digit in board.pencilmarks(idx) {
+                marks |= bit(*digit);
+            }
+            grader.candidates[idx] &= marks;
+        }
+        return grader;
+    }
+    pub fn candidates(&self, idx: usize) -> Vec<u8> {
+        return digits_of(self.candidates[idx]).collect();
+    }
+    pub fn to_board(&self) -> Board {
+        let mut digits = [None; 81];
+        for (digit, cell) in digits.iter_mut().zip(self.cells.iter()) {
+            if *cell != 0 {
+                *digit = Some(*cell);
+            }
+        }
+        let mut board = Board::from_digits(digits);
+        for idx in 0..81 {
+            if self.cells[idx] == 0 {
+                board.set_pencilmarks(idx, digits_of(self.candidates[idx]).collect());
+            }
+        }
+        return board;
+    }
+    fn place(&mut self, idx: usize, digit: u8) {
+        self.cells[idx] = digit;
+        self.candidates[idx] = 0;
+        for peer in 0..81 {
+            if sees(idx, peer) {
+                self.candidates[peer] &= !bit(digit);
+            }
+        }
+    }
+    pub fn is_solved(&self) -> bool {
+        return self.cells.iter().all(|cell| *cell != 0);
+    }
+    fn is_broken(&self) -> bool {
+        return (0..81).any(|idx| self.cells[idx] == 0 && self.candidates[idx] == 0);
+    }
+    fn positions(&self, house: usize, digit: u8) -> Vec<usize> {
+        return self.houses[house]
+            .iter()
+            .cloned()
+            .filter(|idx| self.candidates[*idx] & bit(digit) != 0)
+            .collect();
+    }
+    fn apply(&mut self, step: &Step) {
+        for (idx, digit) in &step.eliminations {
+            self.candidates[*idx] &= !bit(*digit);
+        }
+        for (idx, digit) in &step.placements {
+            self.place(*idx, *digit);
+        }
+    }
+
+    fn naked_single(&self) -> Option<Step> {
+        for idx in 0..81 {
+            if self.cells[idx] == 0 && self.candidates[idx].count_ones() == 1 {
+                let digit = digits_of(self.candidates[idx]).next()?;
+                return Some(Step {
+                    technique: Technique::NakedSingle,
+                    pattern: vec![idx],
+                    placements: vec![(idx, digit)],
+                    eliminations: vec![],
+                });
+            }
+        }
+        return None;
+    }
+    fn hidden_single(&self) -> Option<Step> {
+        for house in 0..27 {
+            for digit in 1..10u8 {
+                let positions = self.positions(house, digit);
+                if positions.len() == 1 {
+                    return Some(Step {
+                        technique: Technique::HiddenSingle,
+                        pattern: self.houses[house].to_vec(),
+                        placements: vec![(positions[0], digit)],
+                        eliminations: vec![],
+                    });
+                }
+            }
+        }
+        return None;
+    }
+    // A digit confined to one line within a box (pointing) or to one box within a line
+    // (claiming) can be removed from the rest of the other house.
+    fn locked_candidates(&self) -> Option<Step> {
+        for house in 0..27 {
+            for digit in 1..10u8 {
+                let positions = self.positions(house, digit);
+                if positions.len() < 2 {
+                    continue;
+                }
+                for other in 0..27 {
+                    if other == house {
+                        continue;
+                    }
+                    let other_cells = &self.houses[other];
+                    if !positions.iter().all(|idx| other_cells.contains(idx)) {
+                        continue;
+                    }
+                    let eliminations: Vec<(usize, u8)> = self
+                        .positions(other, digit)
+                        .into_iter()
+                        .filter(|idx| !positions.contains(idx))
+                        .map(|idx| (idx, digit))
+                        .collect();
+                    if !eliminations.is_empty() {
+                        return Some(Step {
+                            technique: Technique::LockedCandidates,
+                            pattern: positions,
+                            placements: vec![],
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+        return None;
+    }
+    // `n` cells in a house whose candidates together are only `n` digits.
+    fn naked_subset(&self, n: usize, technique: Technique) -> Option<Step> {
+        for house in 0..27 {
+            let open: Vec<usize> = self.houses[house]
+                .iter()
+                .cloned()
+                .filter(|idx| {
+                    let count = self.candidates[*idx].count_ones() as usize;
+                    self.cells[*idx] == 0 && count >= 2 && count <= n
+                })
+                .collect();
+            for combo in combinations(&open, n) {
+                let union = combo
+                    .iter()
+                    .fold(0u16, |union, idx| union | self.candidates[*idx]);
+                if union.count_ones() as usize != n {
+                    continue;
+                }
+                let mut eliminations = Vec::<(usize, u8)>::new();
+                for idx in self.houses[house].iter() {
+                    if combo.contains(idx) {
+                        continue;
+                    }
+                    for digit in digits_of(self.candidates[*idx] & union) {
+                        eliminations.push((*idx, digit));
+                    }
+                }
+                if !eliminations.is_empty() {
+                    return Some(Step {
+                        technique,
+                        pattern: combo,
+                        placements: vec![],
+                        eliminations,
+                    });
+                }
+            }
+        }
+        return None;
+    }
+    // `n` digits in a house that only fit in the same `n` cells.
+    fn hidden_subset(&self, n: usize, technique: Technique) -> Option<Step> {
+        for house in 0..27 {
+            let digits: Vec<usize> = (1..10u8)
+                .filter(|digit| {
+                    let count = self.positions(house, *digit).len();
+                    count >= 2 && count <= n
+                })
+                .map(|digit| digit as usize)
+                .collect();
+            for combo in combinations(&digits, n) {
+                let mut cells = Vec::<usize>::new();
+                let mut mask = 0u16;
+                for digit in &combo {
+                    mask |= bit(*digit as u8);
+                    for idx in self.positions(house, *digit as u8) {
+                        if !cells.contains(&idx) {
+                            cells.push(idx);
+                        }
+                    }
+                }
+                if cells.len() != n {
+                    continue;
+                }
+                let mut eliminations = Vec::<(usize, u8)>::new();
+                for idx in &cells {
+                    for digit in digits_of(self.candidates[*idx] & !mask) {
+                        eliminations.push((*idx, digit));
+                    }
+                }
+                if !eliminations.is_empty() {
+                    cells.sort_unstable();
+                    return Some(Step {
+                        technique,
+                        pattern: cells,
+                        placements: vec![],
+                        eliminations,
+                    });
+                }
+            }
+        }
+        return None;
+    }
+    // A digit whose spots in `n` rows all fall in the same `n` columns (or the other way round)
+    // can go nowhere else in those columns.
+    fn fish(&self, n: usize, technique: Technique) -> Option<Step> {
+        for digit in 1..10u8 {
+            for (base, cover) in [(0usize, 9usize), (9, 0)].iter() {
+                let line_of = |idx: usize| if *cover == 9 { idx % 9 } else { idx / 9 };
+                let bases: Vec<usize> = (0..9)
+                    .filter(|line| {
+                        let count = self.positions(base + line, digit).len();
+                        count >= 2 && count <= n
+                    })
+                    .collect();
+                for combo in combinations(&bases, n) {
+                    let mut pattern = Vec::<usize>::new();
+                    let mut cover_lines = Vec::<usize>::new();
+                    for line in &combo {
+                        for idx in self.positions(base + line, digit) {
+                            pattern.push(idx);
+                            if !cover_lines.contains(&line_of(idx)) {
+                                cover_lines.push(line_of(idx));
+                            }
+                        }
+                    }
+                    if cover_lines.len() != n {
+                        continue;
+                    }
+                    let mut eliminations = Vec::<(usize, u8)>::new();
+                    for line in &cover_lines {
+                        for idx in self.positions(cover + line, digit) {
+                            if !pattern.contains(&idx) {
+                                eliminations.push((idx, digit));
+                            }
+                        }
+                    }
+                    if !eliminations.is_empty() {
+                        pattern.sort_unstable();
+                        return Some(Step {
+                            technique,
+                            pattern,
+                            placements: vec![],
+                            eliminations,
+                        });
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    // The easiest deduction available right now, if there is one.
+    pub fn next_step(&self) -> Option<Step> {
+        return self
+            .naked_single()
+            .or_else(|| self.hidden_single())
+            .or_else(|| self.locked_candidates())
+            .or_else(|| self.naked_subset(2, Technique::NakedPair))
+            .or_else(|| self.hidden_subset(2, Technique::HiddenPair))
+            .or_else(|| self.naked_subset(3, Technique::NakedTriple))
+            .or_else(|| self.hidden_subset(3, Technique::HiddenTriple))
+            .or_else(|| self.fish(2, Technique::XWing))
+            .or_else(|| self.fish(3, Technique::Swordfish));
+    }
+    pub fn step(&mut self) -> Option<Step> {
+        if self.is_broken() {
+            return None;
+        }
+        let step = self.next_step()?;
+        self.apply(&step);
+        return Some(step);
+    }
+    pub fn run(mut self) -> Grade {
+        let mut steps = Vec::<Step>::new();
+        while !self.is_solved() {
+            match self.step() {
+                Some(step) => steps.push(step),
+                None => break,
+            }
+        }
+        let solved = self.is_solved();
+        let hardest = if solved {
+            steps
+                .iter()
+                .map(|step| step.technique)
+                .max()
+                .unwrap_or(Technique::NakedSingle)
+        } else {
+            Technique::Backtracking
+        };
+        return Grade {
+            difficulty: hardest.difficulty(),
+            hardest,
+            solved,
+            steps,
+        };
+    }
+}
+
+pub fn grade(board: &Board) -> Grade {
+    return Grader::new(board).run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_nyt_boards() {
+        let easy = grade(&crate::catalog::test_board("nyt-easy"));
+        assert!(easy.solved);
+        assert_eq!(easy.difficulty, Difficulty::Easy);
+
+        let hard = grade(&crate::catalog::test_board("nyt-hard"));
+        assert!(hard.difficulty > Difficulty::Easy);
+        assert!(hard.solved);
+    }
+
+    #[test]
+    fn test_x_wing() {
+        // Candidates for 1 in rows 0 and 4 only in columns 2 and 6.
+        let mut grader = Grader::new(&Board::from_digits([None; 81]));
+        for row in [0usize, 4].iter() {
+            for col in 0..9 {
+                if col != 2 && col != 6 {
+                    grader.candidates[9 * row + col] &= !bit(1);
+                }
+            }
+        }
+        let step = grader.fish(2, Technique::XWing).unwrap();
+        assert_eq!(step.pattern, vec![2, 6, 38, 42]);
+        assert!(step.eliminations.contains(&(9 * 8 + 2, 1)));
+        assert!(step.eliminations.iter().all(|(idx, digit)| *digit == 1
+            && (idx % 9 == 2 || idx % 9 == 6)
+            && idx / 9 != 0
+            && idx / 9 != 4));
+    }
+}
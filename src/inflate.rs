@@ -0,0 +1,282 @@
+use crate::png;
+
+// Decompression for zlib streams, which is what PNG image data is. Follows RFC 1950 and 1951:
+// stored, fixed-code and dynamic-code blocks, with the codes decoded canonically a bit at a
+// time. Plenty fast for the image sizes a screenshot comes in. The caller says how much output
+// to expect, and a stream that inflates past it is cut off rather than left to fill memory.
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or("compressed data ends early")?;
+        let bit = (byte as u32 >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        return Ok(bit);
+    }
+    // `n` bits, least significant first.
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        return Ok(value);
+    }
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// A canonical Huffman code: how many codes there are of each length, and the symbols in code
+// order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::<u16>::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, l)| **l == length) {
+                symbols.push(symbol as u16);
+            }
+        }
+        return Huffman { counts, symbols };
+    }
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= bits.bit()? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return Err("bad Huffman code".to_string());
+    }
+}
+
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(crate) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// The order code length code lengths come in, in a dynamic block's header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const TOO_LONG: &str = "decompressed data is longer than expected";
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    return (Huffman::new(&lengths), Huffman::new(&[5; 30]));
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for position in CODE_LENGTH_ORDER.iter().take(code_lengths) {
+        lengths[*position] = bits.bits(3)? as u8;
+    }
+    let length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::<u8>::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let symbol = length_code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeat with no previous length")?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() != literals + distances {
+        return Err("code lengths overrun".to_string());
+    }
+    return Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ));
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literal.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                if code >= 29 {
+                    return Err("bad length code".to_string());
+                }
+                let length =
+                    LENGTH_BASE[code] as usize + bits.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                let code = distance.decode(bits)? as usize;
+                if code >= 30 {
+                    return Err("bad distance code".to_string());
+                }
+                let back =
+                    DISTANCE_BASE[code] as usize + bits.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+                if back > out.len() {
+                    return Err("distance reaches before the start".to_string());
+                }
+                // Copies byte by byte, since a match can overlap what it's copying.
+                let start = out.len() - back;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+        if out.len() > limit {
+            return Err(TOO_LONG.to_string());
+        }
+    }
+}
+
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 6 {
+        return Err("zlib stream too short".to_string());
+    }
+    let (cmf, flags) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flags as u16).is_multiple_of(31) {
+        return Err("not a zlib stream".to_string());
+    }
+    if flags & 0x20 != 0 {
+        return Err("zlib preset dictionaries aren't supported".to_string());
+    }
+    let mut bits = Bits {
+        data: &data[2..],
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::<u8>::new();
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = bits
+                    .data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or("compressed data ends early")?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("stored block length doesn't check".to_string());
+                }
+                let start = bits.pos + 4;
+                let block = bits
+                    .data
+                    .get(start..start + length as usize)
+                    .ok_or("compressed data ends early")?;
+                if out.len() + block.len() > limit {
+                    return Err(TOO_LONG.to_string());
+                }
+                out.extend_from_slice(block);
+                bits.pos = start + length as usize;
+            }
+            1 => {
+                let (literal, distance) = fixed_codes();
+                inflate_block(&mut bits, &mut out, limit, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, limit, &literal, &distance)?;
+            }
+            _ => return Err("bad block type".to_string()),
+        }
+        if last {
+            break;
+        }
+    }
+    bits.align();
+    let checksum = bits
+        .data
+        .get(bits.pos..bits.pos + 4)
+        .ok_or("zlib checksum missing")?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]])
+        != png::adler32(&out)
+    {
+        return Err("zlib checksum doesn't match".to_string());
+    }
+    return Ok(out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zlib_decompress() {
+        // zlib.compress(b"sudoku " * 20, 9): a literal run and then one long match
+        let fixed = [
+            0x78, 0xda, 0x2b, 0x2e, 0x4d, 0xc9, 0xcf, 0x2e, 0x55, 0x28, 0x1e, 0x0c, 0x14, 0x00,
+            0x1e, 0x4b, 0x36, 0x9d,
+        ];
+        assert_eq!(
+            zlib_decompress(&fixed, 140).unwrap(),
+            "sudoku ".repeat(20).into_bytes()
+        );
+        let mut broken = fixed;
+        broken[12] ^= 1;
+        assert!(zlib_decompress(&broken, 140).is_err());
+        // the one match alone is longer than this
+        assert!(zlib_decompress(&fixed, 100).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+use crate::raster::{self, Canvas};
+
+// Reads baseline JPEGs, the kind phones and screenshot tools write: 8-bit samples, Huffman
+// coding, greyscale or YCbCr with any chroma subsampling, and restart markers. Progressive and
+// arithmetic-coded files are refused. Chroma is upsampled by repeating samples, which is plenty
+// for finding a grid.
+
+#[rustfmt::skip]
+const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The largest magnitude categories the baseline process uses, for DC differences and AC
+// coefficients. Anything bigger is a corrupt table.
+const MAX_DC_SIZE: u32 = 11;
+const MAX_AC_SIZE: u32 = 10;
+
+#[derive(Clone, Default)]
+struct Huffman {
+    counts: [u16; 17],
+    symbols: Vec<u8>,
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    // Samples for the whole MCU-aligned image, `stride` to a row.
+    samples: Vec<u8>,
+    stride: usize,
+    predictor: i32,
+}
+
+// Entropy-coded data, with the 0xFF 0x00 stuffing taken out as it's read. Hitting a marker
+// reads as zeros, as the standard asks.
+struct Scan<'a> {
+    data: &'a [u8],
+    pos: usize,
+    value: u32,
+    bits: u32,
+}
+
+impl Scan<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.bits == 0 {
+            let byte = self.data.get(self.pos).cloned().unwrap_or(0);
+            self.value = match (byte, self.data.get(self.pos + 1)) {
+                (0xff, Some(0x00)) => {
+                    self.pos += 2;
+                    0xff
+                }
+                // A marker, which stays put for whoever reads it next.
+                (0xff, _) => 0,
+                (byte, _) => {
+                    self.pos += 1;
+                    byte as u32
+                }
+            };
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        return (self.value >> self.bits) & 1;
+    }
+    fn bits(&mut self, n: u32) -> i32 {
+        let mut value = 0;
+        for _ in 0..n {
+            value = (value << 1) | self.bit() as i32;
+        }
+        return value;
+    }
+    // An `n`-bit magnitude, where a leading 0 bit means negative. `n` comes straight from the
+    // file, so it's checked against the largest the coefficient can need.
+    fn extend(&mut self, n: u32, max: u32) -> Result<i32, String> {
+        if n > max {
+            return Err(format!("JPEG magnitude category {} is malformed", n));
+        }
+        if n == 0 {
+            return Ok(0);
+        }
+        let value = self.bits(n);
+        if value < 1 << (n - 1) {
+            return Ok(value - (1 << n) + 1);
+        }
+        return Ok(value);
+    }
+    fn decode(&mut self, table: &Huffman) -> Result<u8, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..17 {
+            code |= self.bit() as i32;
+            let count = table.counts[length] as i32;
+            if code - first < count {
+                return table
+                    .symbols
+                    .get((index + code - first) as usize)
+                    .cloned()
+                    .ok_or("bad Huffman code".to_string());
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return Err("bad Huffman code".to_string());
+    }
+    // Skips to just past the restart marker that should come next.
+    fn restart(&mut self) -> Result<(), String> {
+        self.bits = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xff && (0xd0..=0xd7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return Ok(());
+            }
+            self.pos += 1;
+        }
+        return Err("missing restart marker".to_string());
+    }
+}
+
+// cos((2x + 1)uπ/16), with the 1/√2 for u = 0 folded in.
+fn idct_table() -> [[f32; 8]; 8] {
+    let mut table = [[0f32; 8]; 8];
+    for (x, row) in table.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            *value = scale * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    return table;
+}
+
+fn idct(coefficients: &[i32; 64], table: &[[f32; 8]; 8]) -> [u8; 64] {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            rows[8 * y + x] = (0..8)
+                .map(|u| table[x][u] * coefficients[8 * y + u] as f32)
+                .sum();
+        }
+    }
+    let mut out = [0u8; 64];
+    for x in 0..8 {
+        for y in 0..8 {
+            let value: f32 = (0..8).map(|v| table[y][v] * rows[8 * v + x]).sum();
+            out[8 * y + x] = (value / 4.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    return out;
+}
+
+fn segment(bytes: &[u8], pos: usize) -> Result<&[u8], String> {
+    let length = bytes
+        .get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+        .ok_or("JPEG ends early")?;
+    return bytes
+        .get(pos + 2..pos + length)
+        .ok_or("JPEG segment runs past the end".to_string());
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Canvas, String> {
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return Err("not a JPEG".to_string());
+    }
+    let mut quantization = [[0u16; 64]; 4];
+    let mut dc_tables: Vec<Huffman> = vec![Huffman::default(); 4];
+    let mut ac_tables: Vec<Huffman> = vec![Huffman::default(); 4];
+    let mut components = Vec::<Component>::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let mut restart_interval = 0usize;
+    let mut decoded = false;
+    let table = idct_table();
+
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xff {
+            pos += 1;
+            continue;
+        }
+        let marker = bytes[pos + 1];
+        pos += 2;
+        match marker {
+            0xd8 | 0x01 | 0xd0..=0xd7 | 0xff => continue,
+            0xd9 => break,
+            _ => {}
+        }
+        let body = segment(bytes, pos)?;
+        match marker {
+            0xdb => {
+                let mut at = 0;
+                while at < body.len() {
+                    let (precision, id) = (body[at] >> 4, (body[at] & 3) as usize);
+                    at += 1;
+                    if body.len() < at + if precision == 0 { 64 } else { 128 } {
+                        return Err("quantization table runs past its segment".to_string());
+                    }
+                    for k in 0..64 {
+                        quantization[id][k] = if precision == 0 {
+                            body[at + k] as u16
+                        } else {
+                            u16::from_be_bytes([body[at + 2 * k], body[at + 2 * k + 1]])
+                        };
+                    }
+                    at += if precision == 0 { 64 } else { 128 };
+                }
+            }
+            0xc4 => {
+                let mut at = 0;
+                while at + 17 <= body.len() {
+                    let (class, id) = (body[at] >> 4, (body[at] & 3) as usize);
+                    let mut huffman = Huffman::default();
+                    for length in 1..17 {
+                        huffman.counts[length] = body[at + length] as u16;
+                    }
+                    let total: usize = huffman.counts.iter().map(|c| *c as usize).sum();
+                    huffman.symbols = body
+                        .get(at + 17..at + 17 + total)
+                        .ok_or("Huffman table runs past its segment")?
+                        .to_vec();
+                    at += 17 + total;
+                    if class == 0 {
+                        dc_tables[id] = huffman;
+                    } else {
+                        ac_tables[id] = huffman;
+                    }
+                }
+            }
+            0xc0 | 0xc1 => {
+                if body.len() < 6 || body.len() < 6 + 3 * body[5] as usize {
+                    return Err("JPEG frame header too short".to_string());
+                }
+                if body[0] != 8 {
+                    return Err("only 8-bit JPEGs are supported".to_string());
+                }
+                height = u16::from_be_bytes([body[1], body[2]]) as usize;
+                width = u16::from_be_bytes([body[3], body[4]]) as usize;
+                raster::check_size(width as u32, height as u32)?;
+                for c in body[6..].chunks(3).take(body[5] as usize) {
+                    components.push(Component {
+                        id: c[0],
+                        h: (c[1] >> 4).max(1) as usize,
+                        v: (c[1] & 15).max(1) as usize,
+                        quantization: (c[2] & 3) as usize,
+                        dc_table: 0,
+                        ac_table: 0,
+                        samples: vec![],
+                        stride: 0,
+                        predictor: 0,
+                    });
+                }
+                if components.len() != 1 && components.len() != 3 {
+                    return Err(format!(
+                        "JPEGs with {} colour components aren't supported",
+                        components.len()
+                    ));
+                }
+            }
+            0xc2 | 0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => {
+                return Err("only baseline JPEGs are supported, not progressive".to_string());
+            }
+            0xdd => {
+                if body.len() < 2 {
+                    return Err("JPEG restart interval segment too short".to_string());
+                }
+                restart_interval = u16::from_be_bytes([body[0], body[1]]) as usize;
+            }
+            0xda => {
+                if components.is_empty() {
+                    return Err("JPEG scan before its frame header".to_string());
+                }
+                let count = body.first().cloned().unwrap_or(0) as usize;
+                if body.len() < 1 + 2 * count + 3 {
+                    return Err("JPEG scan header too short".to_string());
+                }
+                if count != components.len() {
+                    return Err(
+                        "JPEGs with separate scans per component aren't supported".to_string()
+                    );
+                }
+                for c in body[1..].chunks(2).take(count) {
+                    let component = components
+                        .iter_mut()
+                        .find(|component| component.id == c[0])
+                        .ok_or("scan names an unknown component")?;
+                    component.dc_table = (c[1] >> 4) as usize & 3;
+                    component.ac_table = (c[1] & 15) as usize & 3;
+                }
+                let mut scan = Scan {
+                    data: bytes,
+                    pos: pos + body.len() + 2,
+                    value: 0,
+                    bits: 0,
+                };
+                decode_scan(
+                    &mut scan,
+                    &mut components,
+                    (width, height),
+                    restart_interval,
+                    &quantization,
+                    (&dc_tables, &ac_tables),
+                    &table,
+                )?;
+                decoded = true;
+                pos = scan.pos;
+                continue;
+            }
+            _ => {}
+        }
+        pos += body.len() + 2;
+    }
+    if !decoded {
+        return Err("JPEG has no image data".to_string());
+    }
+    return Ok(to_canvas(&components, width, height));
+}
+
+fn decode_scan(
+    scan: &mut Scan,
+    components: &mut [Component],
+    (width, height): (usize, usize),
+    restart_interval: usize,
+    quantization: &[[u16; 64]; 4],
+    (dc_tables, ac_tables): (&[Huffman], &[Huffman]),
+    table: &[[f32; 8]; 8],
+) -> Result<(), String> {
+    let h_max = components.iter().map(|c| c.h).max().unwrap();
+    let v_max = components.iter().map(|c| c.v).max().unwrap();
+    let mcus_across = width.div_ceil(8 * h_max);
+    let mcus_down = height.div_ceil(8 * v_max);
+    for component in components.iter_mut() {
+        component.stride = mcus_across * component.h * 8;
+        component.samples = vec![0; component.stride * mcus_down * component.v * 8];
+        component.predictor = 0;
+    }
+    for mcu in 0..mcus_across * mcus_down {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            scan.restart()?;
+            for component in components.iter_mut() {
+                component.predictor = 0;
+            }
+        }
+        let (mcu_x, mcu_y) = (mcu % mcus_across, mcu / mcus_across);
+        for component in components.iter_mut() {
+            for block in 0..component.h * component.v {
+                let mut coefficients = [0i32; 64];
+                let q = &quantization[component.quantization];
+                let size = scan.decode(&dc_tables[component.dc_table])?;
+                // Corrupt data can walk the predictor anywhere; the IDCT clamps what comes out.
+                component.predictor = component
+                    .predictor
+                    .wrapping_add(scan.extend(size as u32, MAX_DC_SIZE)?);
+                coefficients[0] = component.predictor.wrapping_mul(q[0] as i32);
+                let mut k = 1;
+                while k < 64 {
+                    let symbol = scan.decode(&ac_tables[component.ac_table])?;
+                    let (run, size) = ((symbol >> 4) as usize, (symbol & 15) as u32);
+                    if size == 0 {
+                        if run != 15 {
+                            break;
+                        }
+                        k += 16;
+                        continue;
+                    }
+                    k += run;
+                    if k > 63 {
+                        return Err("JPEG block overruns".to_string());
+                    }
+                    coefficients[ZIGZAG[k]] = scan.extend(size, MAX_AC_SIZE)? * q[k] as i32;
+                    k += 1;
+                }
+                let pixels = idct(&coefficients, table);
+                let left = (mcu_x * component.h + block % component.h) * 8;
+                let top = (mcu_y * component.v + block / component.h) * 8;
+                for y in 0..8 {
+                    let start = (top + y) * component.stride + left;
+                    component.samples[start..start + 8].copy_from_slice(&pixels[8 * y..8 * y + 8]);
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn to_canvas(components: &[Component], width: usize, height: usize) -> Canvas {
+    let h_max = components.iter().map(|c| c.h).max().unwrap();
+    let v_max = components.iter().map(|c| c.v).max().unwrap();
+    let mut canvas = Canvas::new(width as u32, height as u32, [255, 255, 255]);
+    let sample = |component: &Component, x: usize, y: usize| -> f32 {
+        let (sx, sy) = (x * component.h / h_max, y * component.v / v_max);
+        return component.samples[sy * component.stride + sx] as f32;
+    };
+    for y in 0..height {
+        for x in 0..width {
+            let luma = sample(&components[0], x, y);
+            canvas.pixels[y * width + x] = if components.len() == 1 {
+                [luma as u8; 3]
+            } else {
+                let cb = sample(&components[1], x, y) - 128.0;
+                let cr = sample(&components[2], x, y) - 128.0;
+                let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                [
+                    clamp(luma + 1.402 * cr),
+                    clamp(luma - 0.344_136 * cb - 0.714_136 * cr),
+                    clamp(luma + 1.772 * cb),
+                ]
+            };
+        }
+    }
+    return canvas;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // A 20x12 gradient, red across and green down, with 4:2:0 chroma and a restart marker
+        // after every MCU
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/images/gradient.jpg");
+        let bytes = std::fs::read(path).unwrap();
+        let canvas = decode(&bytes).unwrap();
+        assert_eq!((canvas.width, canvas.height), (20, 12));
+        for y in 0..12 {
+            for x in 0..20 {
+                let expected = [12 * x as i32, 20 * y as i32, 128];
+                let pixel = canvas.pixel(x, y);
+                for channel in 0..3 {
+                    assert!((pixel[channel] as i32 - expected[channel]).abs() <= 16);
+                }
+            }
+        }
+
+        let mut progressive = bytes.clone();
+        let frame = bytes.windows(2).position(|w| w == [0xff, 0xc0]).unwrap();
+        progressive[frame + 1] = 0xc2;
+        assert!(decode(&progressive).err().unwrap().contains("progressive"));
+        assert!(decode(b"GIF89a").is_err());
+
+        // segments too short for what they have to hold
+        let empty_restart = [&bytes[..2], &[0xff, 0xdd, 0, 2], &bytes[2..]].concat();
+        assert!(decode(&empty_restart).is_err());
+        let scan = bytes.windows(2).position(|w| w == [0xff, 0xda]).unwrap();
+        let empty_scan = [&bytes[..scan], &[0xff, 0xda, 0, 2]].concat();
+        assert!(decode(&empty_scan).is_err());
+
+        // a frame can claim 65535x65535, which is refused before anything is allocated
+        let mut huge = bytes.clone();
+        huge[frame + 5..frame + 9].copy_from_slice(&[0xff; 4]);
+        assert!(decode(&huge).err().unwrap().contains("too large"));
+
+        // a DC table whose symbols are all category 15, which would shift past any predictor
+        let dht = bytes.windows(2).position(|w| w == [0xff, 0xc4]).unwrap();
+        assert_eq!(bytes[dht + 4] >> 4, 0);
+        let count: usize = bytes[dht + 5..dht + 21].iter().map(|n| *n as usize).sum();
+        let mut bad_dc = bytes.clone();
+        for symbol in bad_dc[dht + 21..dht + 21 + count].iter_mut() {
+            *symbol = 15;
+        }
+        assert!(decode(&bad_dc).err().unwrap().contains("malformed"));
+    }
+}
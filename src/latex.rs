@@ -0,0 +1,259 @@
+use crate::board::Board;
+use crate::budget::{Budget, Meter, Outcome};
+use crate::grade::{Grader, Step, Technique};
+use crate::render::{self, RenderOptions};
+
+// LaTeX source for boards and solve traces, for pasting into course notes. Grids are written
+// with the macros in `PREAMBLE` rather than raw TikZ, so a document can restyle every grid in
+// one place. A grid is a `sudoku` environment holding one macro per cell:
+//
+//     \begin{sudoku}
+//     \sudokugiven{0}{0}{4}
+//     \sudokucandidates{2}{0}{3}
+//     \sudokustrike{5}{1}{2}
+//     \end{sudoku}
+//
+// Arguments are column, row and digit, counting columns and rows from 0 at the top left.
+
+// Colours match the SVG renderer's.
+fn colour(name: &str, hex: &str) -> String {
+    return format!(
+        "\\definecolor{{{}}}{{HTML}}{{{}}}\n",
+        name,
+        hex.trim_start_matches('#').to_uppercase()
+    );
+}
+
+pub fn preamble() -> String {
+    let mut preamble = String::from("\\usepackage{tikz}\n");
+    preamble.push_str(&colour("sudokuhighlight", render::HIGHLIGHT_CELL));
+    preamble.push_str(&colour("sudokukeep", render::HIGHLIGHT_CANDIDATE));
+    preamble.push_str(&colour("sudokustruck", render::STRUCK_CANDIDATE));
+    preamble.push_str(&colour("sudokuplayer", render::PLAYER_DIGIT));
+    preamble.push_str(&colour("sudokupencil", render::PENCILMARK));
+    preamble.push_str(PREAMBLE);
+    return preamble;
+}
+
+const PREAMBLE: &str = r"\newlength{\sudokucellsize}
+\setlength{\sudokucellsize}{0.7cm}
+\newenvironment{sudoku}
+  {\begin{tikzpicture}[x=\sudokucellsize,y=-\sudokucellsize]}
+  {\draw[very thin,gray] (0,0) grid (9,9);
+   \draw[thick] (0,0) grid[step=3] (9,9);
+   \end{tikzpicture}}
+\newcommand{\sudokucentre}[2]{({#1+0.5},{#2+0.5})}
+\newcommand{\sudokucorner}[3]{({#1+(mod(#3-1,3)+0.5)/3},{#2+(floor((#3-1)/3)+0.5)/3})}
+\newcommand{\sudokuhighlight}[2]{\fill[sudokuhighlight] (#1,#2) rectangle ++(1,1);}
+\newcommand{\sudokugiven}[3]{\node[font=\bfseries\large] at \sudokucentre{#1}{#2} {#3};}
+\newcommand{\sudokudigit}[3]{\node[font=\large,text=sudokuplayer] at \sudokucentre{#1}{#2} {#3};}
+\newcommand{\sudokucandidate}[3]{\node[font=\tiny,text=sudokupencil] at \sudokucorner{#1}{#2}{#3} {#3};}
+\newcommand{\sudokucandidates}[3]{\foreach \x in {#3} {\sudokucandidate{#1}{#2}{\x}}}
+\newcommand{\sudokukeep}[3]{\fill[sudokukeep] \sudokucorner{#1}{#2}{#3} circle (0.14);
+  \sudokucandidate{#1}{#2}{#3}}
+\newcommand{\sudokustrike}[3]{\fill[sudokustruck] \sudokucorner{#1}{#2}{#3} circle (0.14);
+  \sudokucandidate{#1}{#2}{#3}}
+\newcommand{\sudokustep}[3]{\paragraph{Step #1: #2.} #3\par\nopagebreak}
+";
+
+// One grid. Uses the same options as the SVG renderer, less the cell size, which is the
+// `\sudokucellsize` length.
+pub fn grid(board: &Board, options: &RenderOptions) -> String {
+    let mut tex = String::from("\\begin{sudoku}\n");
+    for idx in &options.highlighted_cells {
+        tex.push_str(&format!(
+            "\\sudokuhighlight{{{}}}{{{}}}\n",
+            idx % 9,
+            idx / 9
+        ));
+    }
+    for idx in 0..81 {
+        let (col, row) = (idx % 9, idx / 9);
+        if let Some(digit) = board.digit(idx) {
+            let command = if options.is_given(board, idx) {
+                "sudokugiven"
+            } else {
+                "sudokudigit"
+            };
+            tex.push_str(&format!(
+                "\\{}{{{}}}{{{}}}{{{}}}\n",
+                command, col, row, digit
+            ));
+            continue;
+        }
+        if !options.show_pencilmarks {
+            continue;
+        }
+        let mut marks: Vec<u8> = board.pencilmarks(idx).iter().cloned().collect();
+        marks.sort_unstable();
+        let mut plain = Vec::<String>::new();
+        for digit in marks {
+            if options.struck_candidates.contains(&(idx, digit)) {
+                tex.push_str(&format!(
+                    "\\sudokustrike{{{}}}{{{}}}{{{}}}\n",
+                    col, row, digit
+                ));
+            } else if options.highlighted_candidates.contains(&(idx, digit)) {
+                tex.push_str(&format!(
+                    "\\sudokukeep{{{}}}{{{}}}{{{}}}\n",
+                    col, row, digit
+                ));
+            } else {
+                plain.push(digit.to_string());
+            }
+        }
+        if !plain.is_empty() {
+            tex.push_str(&format!(
+                "\\sudokucandidates{{{}}}{{{}}}{{{}}}\n",
+                col,
+                row,
+                plain.join(",")
+            ));
+        }
+    }
+    tex.push_str("\\end{sudoku}\n");
+    return tex;
+}
+
+fn technique_name(technique: Technique) -> &'static str {
+    return match technique {
+        Technique::NakedSingle => "Naked single",
+        Technique::HiddenSingle => "Hidden single",
+        Technique::LockedCandidates => "Locked candidates",
+        Technique::NakedPair => "Naked pair",
+        Technique::HiddenPair => "Hidden pair",
+        Technique::NakedTriple => "Naked triple",
+        Technique::HiddenTriple => "Hidden triple",
+        Technique::XWing => "X-wing",
+        Technique::Swordfish => "Swordfish",
+        Technique::Backtracking => "Backtracking",
+    };
+}
+
+fn cell_name(idx: usize) -> String {
+    return format!("r{}c{}", idx / 9 + 1, idx % 9 + 1);
+}
+
+// "Places 5 in r3c4. Removes 2 from r1c1, r1c2."
+fn describe(step: &Step) -> String {
+    let mut sentences = Vec::<String>::new();
+    for (idx, digit) in &step.placements {
+        sentences.push(format!("Places {} in {}.", digit, cell_name(*idx)));
+    }
+    let mut digits: Vec<u8> = step.eliminations.iter().map(|(_, digit)| *digit).collect();
+    digits.sort_unstable();
+    digits.dedup();
+    for digit in digits {
+        let cells: Vec<String> = step
+            .eliminations
+            .iter()
+            .filter(|(_, eliminated)| *eliminated == digit)
+            .map(|(idx, _)| cell_name(*idx))
+            .collect();
+        sentences.push(format!("Removes {} from {}.", digit, cells.join(", ")));
+    }
+    return sentences.join(" ");
+}
+
+// The grader's solve path, one grid per step showing the candidates just before it with the
+// step's cells and candidates marked, then the final position. A trace that gets stuck ends
+// with a note saying so rather than a solution.
+pub fn trace(puzzle: &Board) -> String {
+    return trace_within(puzzle, &Budget::unlimited()).result;
+}
+
+// `trace`, charging a step of the budget for each of the grader's. A trace cut short by the
+// budget ends where it stopped, with a note saying why.
+pub fn trace_within(puzzle: &Board, budget: &Budget) -> Outcome<String> {
+    let mut meter = Meter::new(budget);
+    let mut grader = Grader::new(puzzle);
+    let mut tex = String::new();
+    let mut number = 0;
+    let mut stopped = None;
+    while !grader.is_solved() {
+        stopped = meter.step();
+        if stopped.is_some() {
+            break;
+        }
+        let before = grader.to_board();
+        let step = match grader.step() {
+            Some(step) => step,
+            None => break,
+        };
+        number += 1;
+        tex.push_str(&format!(
+            "\\sudokustep{{{}}}{{{}}}{{{}}}\n",
+            number,
+            technique_name(step.technique),
+            describe(&step)
+        ));
+        let options = RenderOptions::new()
+            .with_givens(puzzle.clone())
+            .with_step(&step);
+        tex.push_str(&grid(&before, &options));
+        tex.push('\n');
+    }
+    let options = RenderOptions::new().with_givens(puzzle.clone());
+    if grader.is_solved() {
+        tex.push_str("\\paragraph{Solved.}\n");
+    } else if let Some(reason) = stopped {
+        tex.push_str(&format!(
+            "\\paragraph{{Stopped.}} Out of budget ({}).\n",
+            reason
+        ));
+    } else {
+        tex.push_str("\\paragraph{Stuck.} No further logical step; the rest needs guessing.\n");
+    }
+    tex.push_str(&grid(&grader.to_board(), &options));
+    return meter.finish(tex, stopped);
+}
+
+// A complete document around `body`, ready for `pdflatex`.
+pub fn document(body: &str) -> String {
+    return format!(
+        "\\documentclass{{article}}\n{}\\begin{{document}}\n{}\\end{{document}}\n",
+        preamble(),
+        body
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let mut options = RenderOptions::new();
+        options.show_pencilmarks = false;
+        let tex = grid(&puzzle, &options);
+        assert!(tex.starts_with("\\begin{sudoku}\n\\sudokugiven{0}{0}{4}\n"));
+        assert_eq!(tex.matches("\\sudokugiven").count(), 38);
+        assert_eq!(tex.lines().count(), 40);
+    }
+
+    #[test]
+    fn test_trace() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let tex = trace(&puzzle);
+        let steps = crate::grade::grade(&puzzle).steps.len();
+        assert_eq!(tex.matches("\\sudokustep{").count(), steps);
+        assert!(tex.starts_with("\\sudokustep{1}{Naked single}{Places 3 in r1c3.}\n"));
+        // each step's grid shows the candidates left at that point
+        assert!(tex.contains("\\sudokukeep{2}{0}{3}\n"));
+        assert!(tex.contains("\\paragraph{Solved.}"));
+        assert_eq!(tex.matches("\\begin{sudoku}").count(), steps + 1);
+
+        let budget = Budget::unlimited().with_max_steps(3);
+        let short = trace_within(&puzzle, &budget);
+        assert_eq!(short.stopped, crate::budget::StopReason::StepLimit);
+        assert_eq!(short.result.matches("\\sudokustep{").count(), 3);
+        assert!(short
+            .result
+            .contains("\\paragraph{Stopped.} Out of budget (step_limit)."));
+
+        let document = document(&tex);
+        assert!(document.contains("\\newenvironment{sudoku}"));
+        assert!(document.contains("\\definecolor{sudokuplayer}{HTML}{2B5FD9}"));
+    }
+}
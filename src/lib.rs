@@ -0,0 +1,32 @@
+#![allow(clippy::needless_return)]
+
+pub mod batch;
+pub mod board;
+pub mod booklet;
+pub mod budget;
+pub mod candidates;
+pub mod canonical;
+pub mod catalog;
+pub mod check;
+pub mod daily;
+pub mod deflate;
+pub mod envelope;
+pub mod files;
+pub mod fpuzzles;
+pub mod generate;
+pub mod grade;
+pub mod inflate;
+pub mod jpeg;
+pub mod latex;
+pub mod lzstring;
+pub mod minimal;
+pub mod pdf;
+pub mod png;
+pub mod pretty;
+pub mod raster;
+pub mod recognize;
+pub mod render;
+pub mod rng;
+pub mod search;
+pub mod server;
+pub mod transform;
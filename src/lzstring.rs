@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+// The lz-string compression that f-puzzles and SudokuPad use to fit a puzzle's JSON into a URL,
+// in its base64 flavour. lz-string works on JavaScript strings, so text goes in and out as
+// UTF-16 code units; titles with characters outside the BMP survive the round trip.
+
+// The most UTF-16 code units a decompression produces. An f-puzzles 9x9 is a small fraction of
+// this, and a few bytes of input can otherwise ask for far more.
+pub const MAX_OUTPUT: usize = 1 << 20;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Codes are written least significant bit first, each output character holding `bits_per_char`
+// bits with the first one highest.
+struct BitWriter {
+    bits_per_char: u32,
+    value: u32,
+    position: u32,
+    out: Vec<u32>,
+}
+
+impl BitWriter {
+    fn write(&mut self, mut value: u32, bits: u32) {
+        for _ in 0..bits {
+            self.value = (self.value << 1) | (value & 1);
+            value >>= 1;
+            if self.position == self.bits_per_char - 1 {
+                self.out.push(self.value);
+                self.value = 0;
+                self.position = 0;
+            } else {
+                self.position += 1;
+            }
+        }
+    }
+    // Pads the last character with zeros. Like the original this always pushes one more
+    // character, even when the last one was already full.
+    fn finish(mut self) -> Vec<u32> {
+        loop {
+            self.value <<= 1;
+            if self.position == self.bits_per_char - 1 {
+                self.out.push(self.value);
+                return self.out;
+            }
+            self.position += 1;
+        }
+    }
+}
+
+// Code widths grow by a bit each time the dictionary outgrows them.
+struct Width {
+    enlarge_in: u32,
+    bits: u32,
+}
+
+impl Width {
+    fn count(&mut self) {
+        self.enlarge_in -= 1;
+        if self.enlarge_in == 0 {
+            self.enlarge_in = 1 << self.bits;
+            self.bits += 1;
+        }
+    }
+}
+
+fn compress(input: &[u16], bits_per_char: u32) -> Vec<u32> {
+    let mut dictionary = HashMap::<Vec<u16>, u32>::new();
+    // Single characters seen but not yet written out as literals.
+    let mut to_create = HashSet::<u16>::new();
+    let mut width = Width {
+        enlarge_in: 2,
+        bits: 2,
+    };
+    let mut writer = BitWriter {
+        bits_per_char,
+        value: 0,
+        position: 0,
+        out: vec![],
+    };
+    let mut emit = |w: &[u16],
+                    dictionary: &HashMap<Vec<u16>, u32>,
+                    to_create: &mut HashSet<u16>,
+                    width: &mut Width| {
+        if w.len() == 1 && to_create.remove(&w[0]) {
+            if w[0] < 256 {
+                writer.write(0, width.bits);
+                writer.write(w[0] as u32, 8);
+            } else {
+                writer.write(1, width.bits);
+                writer.write(w[0] as u32, 16);
+            }
+            width.count();
+        } else {
+            writer.write(dictionary[w], width.bits);
+        }
+        width.count();
+    };
+
+    let mut w = Vec::<u16>::new();
+    for &c in input {
+        if !dictionary.contains_key(&vec![c]) {
+            dictionary.insert(vec![c], dictionary.len() as u32 + 3);
+            to_create.insert(c);
+        }
+        let mut wc = w.clone();
+        wc.push(c);
+        if dictionary.contains_key(&wc) {
+            w = wc;
+            continue;
+        }
+        emit(&w, &dictionary, &mut to_create, &mut width);
+        dictionary.insert(wc, dictionary.len() as u32 + 3);
+        w = vec![c];
+    }
+    if !w.is_empty() {
+        emit(&w, &dictionary, &mut to_create, &mut width);
+    }
+    writer.write(2, width.bits);
+    return writer.finish();
+}
+
+// The other end of `BitWriter`. Reading past the end gives zeros, as it does in JavaScript.
+struct BitReader<'a> {
+    input: &'a [u32],
+    reset: u32,
+    index: usize,
+    position: u32,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> u32 {
+        let mut result = 0;
+        for bit in 0..bits {
+            let value = self.input.get(self.index).cloned().unwrap_or(0);
+            if value & self.position != 0 {
+                result |= 1 << bit;
+            }
+            self.position >>= 1;
+            if self.position == 0 {
+                self.position = self.reset;
+                self.index += 1;
+            }
+        }
+        return result;
+    }
+}
+
+fn decompress(input: &[u32], reset: u32, limit: usize) -> Result<Vec<u16>, String> {
+    let malformed = || "malformed compressed data".to_string();
+    let mut reader = BitReader {
+        input,
+        reset,
+        index: 0,
+        position: reset,
+    };
+    // Codes 0, 1 and 2 mean an 8-bit literal, a 16-bit literal and the end.
+    let mut dictionary: Vec<Vec<u16>> = vec![vec![], vec![], vec![]];
+    let mut width = Width {
+        enlarge_in: 4,
+        bits: 3,
+    };
+    let first = match reader.read(2) {
+        0 => reader.read(8) as u16,
+        1 => reader.read(16) as u16,
+        2 => return Ok(vec![]),
+        _ => return Err(malformed()),
+    };
+    dictionary.push(vec![first]);
+    let mut w = vec![first];
+    let mut result = w.clone();
+    loop {
+        if reader.index >= input.len() {
+            return Err(malformed());
+        }
+        let mut code = reader.read(width.bits) as usize;
+        match code {
+            0 | 1 => {
+                let literal = reader.read(if code == 0 { 8 } else { 16 }) as u16;
+                dictionary.push(vec![literal]);
+                code = dictionary.len() - 1;
+                width.count();
+            }
+            2 => return Ok(result),
+            _ => {}
+        }
+        let entry = if code < dictionary.len() {
+            dictionary[code].clone()
+        } else if code == dictionary.len() {
+            let mut entry = w.clone();
+            entry.push(w[0]);
+            entry
+        } else {
+            return Err(malformed());
+        };
+        result.extend(&entry);
+        if result.len() > limit {
+            return Err(format!("decompresses to more than {} characters", limit));
+        }
+        let mut next = w;
+        next.push(entry[0]);
+        dictionary.push(next);
+        width.count();
+        w = entry;
+    }
+}
+
+pub fn compress_to_base64(input: &str) -> String {
+    let units: Vec<u16> = input.encode_utf16().collect();
+    let mut out: String = compress(&units, 6)
+        .iter()
+        .map(|value| BASE64[*value as usize] as char)
+        .collect();
+    while !out.len().is_multiple_of(4) {
+        out.push('=');
+    }
+    return out;
+}
+
+pub fn decompress_from_base64(input: &str) -> Result<String, String> {
+    let mut values = Vec::<u32>::with_capacity(input.len());
+    for ch in input.chars() {
+        match BASE64.iter().position(|b| *b as char == ch) {
+            Some(value) => values.push(value as u32),
+            None if ch == '=' => {}
+            None => return Err(format!("not a base64 character: {}", ch)),
+        }
+    }
+    let units = decompress(&values, 32, MAX_OUTPUT)?;
+    return String::from_utf16(&units).map_err(|err| err.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        // Worked by hand: a literal `a` in two code bits and eight value bits, then the end
+        // code in three.
+        assert_eq!(compress_to_base64("a"), "IZA=");
+        assert_eq!(decompress_from_base64("IZA=").unwrap(), "a");
+
+        for text in [
+            "",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "{\"size\":9,\"title\":\"Ünïcödé – 🧩\",\"grid\":[[{},{},{}]]}",
+            "TOBEORNOTTOBEORTOBEORNOT#",
+        ] {
+            let compressed = compress_to_base64(text);
+            assert_eq!(decompress_from_base64(&compressed).unwrap(), text);
+        }
+        assert!(decompress_from_base64("not*base64").is_err());
+
+        let long: Vec<u16> = "a".repeat(1000).encode_utf16().collect();
+        assert!(decompress(&compress(&long, 6), 32, 1000).is_ok());
+        assert!(decompress(&compress(&long, 6), 32, 999).is_err());
+    }
+}
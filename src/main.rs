@@ -1,14 +1,17 @@
-mod board;
-mod consts;
-mod server;
+#![allow(clippy::needless_return)]
+
+use sudokuthings::{board, catalog, server};
 
 #[tokio::main]
 async fn main() {
     println!("Hello, world!");
 
-    let nyt_easy_starting_board = board::Board::new(crate::consts::nyt_easy_map());
+    let catalog = catalog::Catalog::load(&catalog::default_dir()).unwrap();
 
-    let _solved = board::solve(nyt_easy_starting_board);
+    if let Some(nyt_easy) = catalog.get("nyt-easy") {
+        let solved = board::solve(nyt_easy.board(), board::PencilmarkPolicy::Trust);
+        println!("{}", solved);
+    }
 
-    server::server().await;
+    server::server(catalog).await;
 }
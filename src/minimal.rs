@@ -0,0 +1,142 @@
+use crate::board::Board;
+use crate::budget::{Budget, Meter, StopReason};
+use crate::search;
+use serde::Serialize;
+
+// A puzzle is minimal when every clue is needed: taking any one away leaves more than one
+// solution.
+
+#[derive(Serialize)]
+pub struct Minimized {
+    pub puzzle: Board,
+    // The clues that were dropped, as (index, digit).
+    pub redundant: Vec<(usize, u8)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MinimizeError {
+    NotUnique,
+    Unfinished(StopReason),
+}
+
+impl std::fmt::Display for MinimizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MinimizeError::NotUnique => write!(f, "puzzle does not have a unique solution"),
+            MinimizeError::Unfinished(reason) => write!(f, "gave up minimizing: {}", reason),
+        }
+    }
+}
+
+fn clues(board: &Board) -> [Option<u8>; 81] {
+    let mut digits = [None; 81];
+    for (idx, digit) in digits.iter_mut().enumerate() {
+        *digit = board.digit(idx);
+    }
+    return digits;
+}
+
+// Whether the digits have exactly one completion, or why we couldn't tell.
+fn unique_within(digits: &[Option<u8>; 81], meter: &mut Meter) -> Result<bool, StopReason> {
+    let (solutions, stopped) = search::solutions_metered(&Board::from_digits(*digits), 2, meter);
+    if let Some(reason) = stopped {
+        return Err(reason);
+    }
+    return Ok(solutions.len() == 1);
+}
+
+// False for puzzles without a unique solution, as well as for ones with a clue to spare.
+pub fn is_minimal(board: &Board) -> bool {
+    let budget = Budget::unlimited();
+    let mut meter = Meter::new(&budget);
+    let mut digits = clues(board);
+    if unique_within(&digits, &mut meter) != Ok(true) {
+        return false;
+    }
+    for idx in 0..81 {
+        let removed = match digits[idx].take() {
+            Some(digit) => digit,
+            None => continue,
+        };
+        let still_unique = unique_within(&digits, &mut meter) == Ok(true);
+        digits[idx] = Some(removed);
+        if still_unique {
+            return false;
+        }
+    }
+    return true;
+}
+
+pub fn minimize(board: &Board) -> Result<Minimized, MinimizeError> {
+    return minimize_within(board, &Budget::unlimited());
+}
+
+// Drop clues in index order whenever the puzzle stays unique without them. Which clues end up
+// redundant depends on the order, but the result is always minimal. The budget covers the whole
+// minimization, not each uniqueness check in it.
+pub fn minimize_within(board: &Board, budget: &Budget) -> Result<Minimized, MinimizeError> {
+    let mut meter = Meter::new(budget);
+    let mut digits = clues(board);
+    if !unique_within(&digits, &mut meter).map_err(MinimizeError::Unfinished)? {
+        return Err(MinimizeError::NotUnique);
+    }
+    let mut redundant = Vec::<(usize, u8)>::new();
+    for idx in 0..81 {
+        let removed = match digits[idx].take() {
+            Some(digit) => digit,
+            None => continue,
+        };
+        if unique_within(&digits, &mut meter).map_err(MinimizeError::Unfinished)? {
+            redundant.push((idx, removed));
+        } else {
+            digits[idx] = Some(removed);
+        }
+    }
+    return Ok(Minimized {
+        puzzle: Board::from_digits(digits),
+        redundant,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_adds_back_nothing_needed() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let minimized = minimize(&puzzle).unwrap();
+        assert!(is_minimal(&minimized.puzzle));
+        assert_eq!(search::count_solutions(&minimized.puzzle, 2), 1);
+
+        // putting any one redundant clue back makes it non-minimal again
+        let mut map = std::collections::HashMap::new();
+        for idx in 0..81 {
+            if let Some(digit) = minimized.puzzle.digit(idx) {
+                map.insert(idx, digit);
+            }
+        }
+        let (idx, digit) = minimized.redundant[0];
+        map.insert(idx, digit);
+        assert!(!is_minimal(&Board::new(map)));
+    }
+
+    #[test]
+    fn test_budget_covers_every_check() {
+        // enough nodes for the first uniqueness check alone, but not for the ones after it
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let first = search::solutions_within(&puzzle, 2, &Budget::unlimited()).nodes;
+        let budget = Budget::unlimited().with_max_nodes(first);
+        assert_eq!(
+            minimize_within(&puzzle, &budget).err(),
+            Some(MinimizeError::Unfinished(StopReason::NodeLimit))
+        );
+    }
+
+    #[test]
+    fn test_not_unique() {
+        let empty = Board::from_digits([None; 81]);
+        assert!(!is_minimal(&empty));
+        assert_eq!(minimize(&empty).err(), Some(MinimizeError::NotUnique));
+    }
+}
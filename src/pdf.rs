@@ -0,0 +1,155 @@
+// Just enough of PDF to print puzzles: pages of lines and text in the standard Helvetica fonts,
+// which every reader has built in so nothing needs embedding. Streams are left uncompressed.
+
+pub struct Page {
+    pub width: f64,
+    pub height: f64,
+    content: String,
+}
+
+// Standard fonts for `Page::text`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        return match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        };
+    }
+}
+
+// Helvetica's digits and space are all this wide, as a fraction of the font size; other
+// characters are close enough to it for centring short labels.
+const CHAR_WIDTH: f64 = 0.556;
+
+pub fn text_width(text: &str, size: f64) -> f64 {
+    return text.chars().count() as f64 * size * CHAR_WIDTH;
+}
+
+// PDF strings are bytes in the font's encoding; anything outside printable ASCII is swapped for
+// `?` rather than risk garbage.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ' '..='~' => escaped.push(ch),
+            _ => escaped.push('?'),
+        }
+    }
+    return escaped;
+}
+
+// Coordinates are in points from the bottom left, as in PDF itself.
+impl Page {
+    pub fn new(width: f64, height: f64) -> Page {
+        return Page {
+            width,
+            height,
+            content: String::new(),
+        };
+    }
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, width: f64) {
+        self.content.push_str(&format!(
+            "{:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n",
+            width, x1, y1, x2, y2
+        ));
+    }
+    pub fn text(&mut self, x: f64, y: f64, size: f64, font: Font, text: &str) {
+        self.content.push_str(&format!(
+            "BT /{} {:.2} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            y,
+            escape(text)
+        ));
+    }
+    pub fn centred_text(&mut self, x: f64, y: f64, size: f64, font: Font, text: &str) {
+        self.text(x - text_width(text, size) / 2.0, y, size, font, text);
+    }
+}
+
+pub fn write(pages: &[Page]) -> Vec<u8> {
+    let mut objects = Vec::<String>::new();
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 5 + 2 * i))
+        .collect();
+    objects.push(format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        pages.len()
+    ));
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string());
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>".to_string());
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            6 + 2 * i
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            page.content.len(),
+            page.content
+        ));
+    }
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::<usize>::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref = out.len();
+    out.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        out.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    out.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    return out.into_bytes();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_references() {
+        let mut page = Page::new(200.0, 100.0);
+        page.line(0.0, 0.0, 10.0, 10.0, 1.0);
+        page.text(5.0, 5.0, 12.0, Font::Bold, "a (tricky) \\ title");
+        let pdf = String::from_utf8(write(&[page, Page::new(200.0, 100.0)])).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(a \\(tricky\\) \\\\ title) Tj"));
+
+        // every entry in the table points at the start of its object
+        let xref = pdf.find("\nxref\n").unwrap() + 1;
+        let entries: Vec<&str> = pdf[xref..].lines().skip(3).take(8).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+        let startxref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert_eq!(startxref, xref);
+    }
+}
@@ -0,0 +1,342 @@
+// Writes 8-bit RGB PNGs, compressed with `deflate`. Boards are mostly flat colour, which
+// squeezes down to a small fraction of the raw pixels.
+//
+// Reads any non-interlaced PNG back as RGB, for importing screenshots: greyscale, palette and
+// truecolour at every bit depth, with transparency laid over white.
+
+use crate::deflate;
+use crate::inflate;
+use crate::raster::{self, Canvas};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+// The CRC of every byte value, so checksums take a byte at a time rather than a bit.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    return table;
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    return !crc;
+}
+
+pub(crate) fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    return (b << 16) | a;
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// `pixels` holds `width * height` RGB triples, a row at a time from the top.
+pub fn encode(width: u32, height: u32, pixels: &[[u8; 3]]) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height) as usize);
+    let mut out = SIGNATURE.to_vec();
+    let mut header = Vec::<u8>::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type 2 (RGB), then the default compression, filter and interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+
+    let mut raw = Vec::<u8>::with_capacity(pixels.len() * 3 + height as usize);
+    for row in pixels.chunks(width as usize) {
+        // Each row starts with its filter type, none here.
+        raw.push(0);
+        for pixel in row {
+            raw.extend_from_slice(pixel);
+        }
+    }
+    chunk(&mut out, b"IDAT", &deflate::zlib_compress(&raw));
+    chunk(&mut out, b"IEND", &[]);
+    return out;
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u8,
+    colour_type: u8,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        return match self.colour_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        };
+    }
+    fn bits_per_pixel(&self) -> usize {
+        return self.channels() * self.depth as usize;
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        return a;
+    }
+    if pb <= pc {
+        return b;
+    }
+    return c;
+}
+
+// Undoes the per-row filters in place, leaving the rows without their filter bytes.
+fn unfilter(data: &[u8], header: &Header) -> Result<Vec<u8>, String> {
+    let stride = (header.width as usize * header.bits_per_pixel()).div_ceil(8);
+    let bpp = header.bits_per_pixel().div_ceil(8);
+    let height = header.height as usize;
+    if data.len() < height * (stride + 1) {
+        return Err("image data is too short".to_string());
+    }
+    let mut out = vec![0u8; height * stride];
+    for row in 0..height {
+        let filter = data[row * (stride + 1)];
+        let line = &data[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+        for i in 0..stride {
+            let a = if i >= bpp {
+                out[row * stride + i - bpp]
+            } else {
+                0
+            };
+            let b = if row > 0 {
+                out[(row - 1) * stride + i]
+            } else {
+                0
+            };
+            let c = if row > 0 && i >= bpp {
+                out[(row - 1) * stride + i - bpp]
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("unknown filter type {}", filter)),
+            };
+            out[row * stride + i] = line[i].wrapping_add(predicted);
+        }
+    }
+    return Ok(out);
+}
+
+// Sample `index` of a row, scaled to 8 bits.
+fn sample(row: &[u8], index: usize, depth: u8) -> u8 {
+    return match depth {
+        16 => row[2 * index],
+        8 => row[index],
+        _ => {
+            let per_byte = 8 / depth as usize;
+            let shift = 8 - depth as usize * (index % per_byte + 1);
+            let value = (row[index / per_byte] >> shift) & ((1 << depth) - 1);
+            // Scales 1, 2 and 4-bit greys up to the full range.
+            (value as u16 * 255 / ((1 << depth) - 1)) as u8
+        }
+    };
+}
+
+fn over_white(colour: [u8; 3], alpha: u8) -> [u8; 3] {
+    let blend = |c: u8| ((c as u16 * alpha as u16 + 255 * (255 - alpha as u16)) / 255) as u8;
+    return [blend(colour[0]), blend(colour[1]), blend(colour[2])];
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Canvas, String> {
+    if !bytes.starts_with(&SIGNATURE) {
+        return Err("not a PNG".to_string());
+    }
+    let mut header = None;
+    let mut palette = Vec::<[u8; 3]>::new();
+    let mut alphas = Vec::<u8>::new();
+    let mut data = Vec::<u8>::new();
+    let mut pos = 8;
+    while pos + 12 <= bytes.len() {
+        let length =
+            u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+                as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let body = bytes
+            .get(pos + 8..pos + 8 + length)
+            .ok_or("PNG chunk runs past the end")?;
+        match kind {
+            b"IHDR" if length == 13 => {
+                if body[12] != 0 {
+                    return Err("interlaced PNGs aren't supported".to_string());
+                }
+                header = Some(Header {
+                    width: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+                    height: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+                    depth: body[8],
+                    colour_type: body[9],
+                });
+            }
+            b"PLTE" => {
+                if !length.is_multiple_of(3) || length > 3 * 256 {
+                    return Err(format!("bad PNG palette length {}", length));
+                }
+                palette = body.chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
+            }
+            b"tRNS" => alphas = body.to_vec(),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + length;
+    }
+    let header = header.ok_or("PNG has no header")?;
+    let valid_depth = match header.colour_type {
+        0 => [1, 2, 4, 8, 16].contains(&header.depth),
+        3 => [1, 2, 4, 8].contains(&header.depth),
+        2 | 4 | 6 => [8, 16].contains(&header.depth),
+        _ => false,
+    };
+    if !valid_depth {
+        return Err(format!(
+            "bad PNG colour type {} at depth {}",
+            header.colour_type, header.depth
+        ));
+    }
+    raster::check_size(header.width, header.height)?;
+    // Rows of filtered pixels, each with its filter byte; anything beyond is unused.
+    let expected = header.height as usize
+        * ((header.width as usize * header.bits_per_pixel()).div_ceil(8) + 1);
+    let rows = unfilter(&inflate::zlib_decompress(&data, expected)?, &header)?;
+    let stride = rows.len() / header.height.max(1) as usize;
+    let mut canvas = Canvas::new(header.width, header.height, [255, 255, 255]);
+    let channels = header.channels();
+    for (y, row) in rows.chunks(stride.max(1)).enumerate() {
+        for x in 0..header.width as usize {
+            let at = |channel: usize| sample(row, x * channels + channel, header.depth);
+            let pixel = match header.colour_type {
+                0 => [at(0); 3],
+                2 => [at(0), at(1), at(2)],
+                3 => {
+                    // Palette indices are the raw bits, not scaled.
+                    let per_byte = 8 / header.depth as usize;
+                    let index = if header.depth == 8 {
+                        row[x] as usize
+                    } else {
+                        let shift = 8 - header.depth as usize * (x % per_byte + 1);
+                        ((row[x / per_byte] >> shift) & ((1 << header.depth) - 1)) as usize
+                    };
+                    let colour = *palette.get(index).ok_or("palette index out of range")?;
+                    over_white(colour, alphas.get(index).cloned().unwrap_or(255))
+                }
+                4 => over_white([at(0); 3], at(1)),
+                _ => over_white([at(0), at(1), at(2)], at(3)),
+            };
+            canvas.pixels[y * header.width as usize + x] = pixel;
+        }
+    }
+    return Ok(canvas);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        // The usual check values.
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let png = encode(2, 1, &[[255, 0, 0], [0, 0, 255]]);
+        assert_eq!(&png[..8], &SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 2);
+        let idat = &png[33..];
+        assert_eq!(&idat[4..8], b"IDAT");
+        assert_eq!(&idat[8..10], &[0x78, 0x01]);
+        let decoded = decode(&png).unwrap();
+        assert_eq!(decoded.pixels, vec![[255, 0, 0], [0, 0, 255]]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn test_decode() {
+        let pixels: Vec<[u8; 3]> = (0..12u8).map(|i| [i * 20, 255 - i * 20, 7]).collect();
+        let decoded = decode(&encode(4, 3, &pixels)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (4, 3));
+        assert_eq!(decoded.pixels, pixels);
+
+        // Written by zlib: RGBA with each row using the next filter type, then a 2-bit palette
+        // image with a transparent entry.
+        let fixture = |name: &str| {
+            let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/images");
+            return decode(&std::fs::read(dir.join(name)).unwrap()).unwrap();
+        };
+        let filters = fixture("filters.png");
+        assert_eq!((filters.width, filters.height), (5, 5));
+        assert_eq!(filters.pixel(0, 0), [255, 0, 0]);
+        assert_eq!(filters.pixel(2, 2), [120, 120, 80]);
+        assert_eq!(filters.pixel(3, 3), [180, 180, 180]);
+        assert_eq!(filters.pixel(4, 4), [255, 255, 255]);
+        let palette = fixture("palette.png");
+        let first_row: Vec<[u8; 3]> = (0..6).map(|x| palette.pixel(x, 0)).collect();
+        assert_eq!(
+            first_row,
+            vec![
+                [0, 0, 0],
+                [255, 0, 0],
+                [0, 0, 255],
+                [255, 255, 255],
+                [255, 0, 0],
+                [0, 0, 0]
+            ]
+        );
+        assert!(decode(b"GIF89a").is_err());
+
+        // a palette that doesn't split into whole colours, slipped in after the header
+        let encoded = encode(4, 3, &pixels);
+        let mut bad_palette = encoded[..33].to_vec();
+        chunk(&mut bad_palette, b"PLTE", &[0; 4]);
+        bad_palette.extend_from_slice(&encoded[33..]);
+        assert!(decode(&bad_palette).is_err());
+
+        let mut huge = encoded.clone();
+        huge[16..20].copy_from_slice(&100_000u32.to_be_bytes());
+        assert!(decode(&huge).err().unwrap().contains("too large"));
+    }
+}
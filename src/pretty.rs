@@ -0,0 +1,203 @@
+use crate::board::Board;
+use std::fmt;
+
+// Boards drawn as text with box borders. `{}` gives one character per cell and `{:#}` gives
+// each empty cell as a 3x3 block of its candidates, with a placed digit in the middle of an
+// otherwise blank block. Both are ASCII; `board.pretty()` switches to box-drawing characters.
+//
+//     +-------+-------+-------+        +-------------+-------------+-----
+//     | 4 8 . | 1 . . | 6 . 9 |        |         ..3 |     .23 ... |
+//     | . . 7 | . 8 . | 1 . 5 |        |  4   8  ... |  1  .5. .5. |  ...
+//                                      |         ... |     ... ... |
+
+#[derive(Clone, Copy)]
+pub struct Pretty<'a> {
+    board: &'a Board,
+    candidates: bool,
+    unicode: bool,
+}
+
+impl<'a> Pretty<'a> {
+    pub fn candidates(mut self) -> Pretty<'a> {
+        self.candidates = true;
+        return self;
+    }
+    pub fn ascii(mut self) -> Pretty<'a> {
+        self.unicode = false;
+        return self;
+    }
+}
+
+impl Board {
+    pub fn pretty(&self) -> Pretty<'_> {
+        return Pretty {
+            board: self,
+            candidates: false,
+            unicode: true,
+        };
+    }
+}
+
+struct Glyphs {
+    horizontal: char,
+    vertical: char,
+    // Left, middle and right corners for the top, middle and bottom borders.
+    top: [char; 3],
+    middle: [char; 3],
+    bottom: [char; 3],
+    blank: char,
+}
+
+const ASCII: Glyphs = Glyphs {
+    horizontal: '-',
+    vertical: '|',
+    top: ['+', '+', '+'],
+    middle: ['+', '+', '+'],
+    bottom: ['+', '+', '+'],
+    blank: '.',
+};
+
+const UNICODE: Glyphs = Glyphs {
+    horizontal: '─',
+    vertical: '│',
+    top: ['┌', '┬', '┐'],
+    middle: ['├', '┼', '┤'],
+    bottom: ['└', '┴', '┘'],
+    blank: '·',
+};
+
+impl Pretty<'_> {
+    fn glyphs(&self) -> &'static Glyphs {
+        if self.unicode {
+            return &UNICODE;
+        }
+        return &ASCII;
+    }
+    // One text row of a cell: the cell itself in compact mode, or row `line` of its 3x3 block.
+    fn cell_text(&self, idx: usize, line: usize) -> String {
+        let blank = self.glyphs().blank;
+        let digit = self.board.digit(idx);
+        if !self.candidates {
+            return match digit {
+                Some(digit) => digit.to_string(),
+                None => blank.to_string(),
+            };
+        }
+        if let Some(digit) = digit {
+            return if line == 1 {
+                format!(" {} ", digit)
+            } else {
+                "   ".to_string()
+            };
+        }
+        let pencilmarks = self.board.pencilmarks(idx);
+        return (1..4u8)
+            .map(|i| {
+                let candidate = 3 * line as u8 + i;
+                if pencilmarks.contains(&candidate) {
+                    (b'0' + candidate) as char
+                } else {
+                    blank
+                }
+            })
+            .collect();
+    }
+    fn border(&self, f: &mut fmt::Formatter, corners: [char; 3], width: usize) -> fmt::Result {
+        let horizontal = self.glyphs().horizontal.to_string().repeat(width);
+        writeln!(
+            f,
+            "{}{}{}{}{}{}{}",
+            corners[0], horizontal, corners[1], horizontal, corners[1], horizontal, corners[2]
+        )
+    }
+}
+
+impl fmt::Display for Pretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let glyphs = self.glyphs();
+        let (lines_per_cell, cell_width) = if self.candidates { (3, 3) } else { (1, 1) };
+        // Three cells with a space between each and one either side.
+        let width = 3 * cell_width + 4;
+        self.border(f, glyphs.top, width)?;
+        for row in 0..9 {
+            if row == 3 || row == 6 {
+                self.border(f, glyphs.middle, width)?;
+            } else if row > 0 && self.candidates {
+                // A spacer so neighbouring blocks don't run together.
+                let gap = " ".repeat(width);
+                let v = glyphs.vertical;
+                writeln!(f, "{}{}{}{}{}{}{}", v, gap, v, gap, v, gap, v)?;
+            }
+            for line in 0..lines_per_cell {
+                let mut text = String::new();
+                for col in 0..9 {
+                    if col % 3 == 0 {
+                        text.push(glyphs.vertical);
+                    }
+                    text.push(' ');
+                    text.push_str(&self.cell_text(9 * row + col, line));
+                    if col % 3 == 2 {
+                        text.push(' ');
+                    }
+                }
+                text.push(glyphs.vertical);
+                writeln!(f, "{}", text)?;
+            }
+        }
+        self.border(f, glyphs.bottom, width)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pretty = self.pretty().ascii();
+        if f.alternate() {
+            return write!(f, "{}", pretty.candidates());
+        }
+        write!(f, "{}", pretty)
+    }
+}
+
+// Test failures print the whole position rather than raw sets.
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\n{:#}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::PencilmarkPolicy;
+
+    #[test]
+    fn test_compact() {
+        let board = crate::catalog::test_board("nyt-easy");
+        let printed = board.to_string();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "+-------+-------+-------+");
+        assert_eq!(lines[1], "| 4 8 . | 1 . . | 6 . 9 |");
+        assert_eq!(lines[4], "+-------+-------+-------+");
+
+        let unicode = board.pretty().to_string();
+        assert!(unicode.starts_with("┌───────┬"));
+        assert!(unicode.lines().nth(1).unwrap().starts_with("│ 4 8 · │"));
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut board = crate::catalog::test_board("nyt-easy");
+        board.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        let printed = format!("{:#}", board);
+        let lines: Vec<&str> = printed.lines().collect();
+        // 27 lines of cells, 6 spacers and 4 borders
+        assert_eq!(lines.len(), 37);
+        assert!(lines.iter().all(|line| line.chars().count() == 43));
+        // cells 0 and 1 hold 4 and 8, and 3 is all that's left for cell 2
+        assert_eq!(&lines[1][..10], "|         ");
+        assert_eq!(&lines[2][..10], "|  4   8  ");
+        assert_eq!(&lines[1][10..13], "..3");
+        assert_eq!(&lines[2][10..13], "...");
+        assert_eq!(format!("{:?}", board), format!("\n{}", printed));
+    }
+}
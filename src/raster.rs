@@ -0,0 +1,202 @@
+use crate::board::Board;
+use crate::png;
+use crate::render::{self, RenderOptions};
+
+// Boards drawn into pixels, for places that can't show SVG. The layout and colours follow
+// `render::to_svg`; digits come from a small bitmap font scaled to the cell size.
+
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+pub type Colour = [u8; 3];
+
+// The largest image decoders will take: a big photo fits, but a header can't ask for gigabytes.
+pub const MAX_SIDE: u32 = 10_000;
+pub const MAX_PIXELS: u64 = 40_000_000;
+
+// Checks a decoded image's declared size before anything is allocated for it.
+pub fn check_size(width: u32, height: u32) -> Result<(), String> {
+    if width > MAX_SIDE || height > MAX_SIDE || width as u64 * height as u64 > MAX_PIXELS {
+        return Err(format!("image is too large: {}x{}", width, height));
+    }
+    return Ok(());
+}
+
+const WHITE: Colour = [255, 255, 255];
+const BLACK: Colour = [0, 0, 0];
+const GREY: Colour = [0x99, 0x99, 0x99];
+
+// "#rrggbb" to RGB, so the raster shares the SVG's colour constants.
+fn colour(hex: &str) -> Colour {
+    let value = u32::from_str_radix(hex.trim_start_matches('#'), 16).unwrap();
+    return [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Colour) -> Canvas {
+        return Canvas {
+            width,
+            height,
+            pixels: vec![background; width as usize * height as usize],
+        };
+    }
+    pub fn pixel(&self, x: u32, y: u32) -> Colour {
+        return self.pixels[(y * self.width + x) as usize];
+    }
+    // Fills the pixels whose centres fall inside the rectangle, clipped to the canvas.
+    pub fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, colour: Colour) {
+        let clip = |value: f64, limit: u32| (value - 0.5).ceil().clamp(0.0, limit as f64) as u32;
+        for py in clip(y, self.height)..clip(y + height, self.height) {
+            for px in clip(x, self.width)..clip(x + width, self.width) {
+                self.pixels[(py * self.width + px) as usize] = colour;
+            }
+        }
+    }
+    pub fn fill_circle(&mut self, cx: f64, cy: f64, radius: f64, colour: Colour) {
+        let top = (cy - radius).floor().max(0.0) as u32;
+        let left = (cx - radius).floor().max(0.0) as u32;
+        let bottom = ((cy + radius).ceil() as u32).min(self.height);
+        let right = ((cx + radius).ceil() as u32).min(self.width);
+        for py in top..bottom {
+            for px in left..right {
+                let (dx, dy) = (px as f64 + 0.5 - cx, py as f64 + 0.5 - cy);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.pixels[(py * self.width + px) as usize] = colour;
+                }
+            }
+        }
+    }
+    // A digit about `height` pixels tall centred on (`cx`, `cy`). Each dot of the font becomes
+    // a square of whole pixels, since sampling rows away can turn an 8 into a 0.
+    pub fn digit(&mut self, cx: f64, cy: f64, height: f64, digit: u8, colour: Colour) {
+        let glyph = &GLYPHS[(digit - 1) as usize];
+        let scale = (height / 7.0).round().max(1.0);
+        let left = (cx - 2.5 * scale).round();
+        let top = (cy - 3.5 * scale).round();
+        for (row, dots) in glyph.iter().enumerate() {
+            for (col, dot) in dots.bytes().enumerate() {
+                if dot == b'#' {
+                    let (x, y) = (left + col as f64 * scale, top + row as f64 * scale);
+                    self.fill_rect(x, y, scale, scale, colour);
+                }
+            }
+        }
+    }
+}
+
+// Five by seven, one string per row.
+#[rustfmt::skip]
+const GLYPHS: [[&str; 7]; 9] = [
+    ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."],
+    [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"],
+    ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."],
+    ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."],
+    ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."],
+    ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."],
+    ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."],
+    [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."],
+    [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."],
+];
+
+pub fn rasterize(board: &Board, options: &RenderOptions) -> Canvas {
+    let cell = options.cell_size as f64;
+    let margin = (cell / 10.0).max(2.0);
+    let side = 9.0 * cell + 2.0 * margin;
+    let mut canvas = Canvas::new(side.round() as u32, side.round() as u32, WHITE);
+    let x = |col: usize| margin + cell * col as f64;
+
+    for idx in &options.highlighted_cells {
+        canvas.fill_rect(
+            x(idx % 9),
+            x(idx / 9),
+            cell,
+            cell,
+            colour(render::HIGHLIGHT_CELL),
+        );
+    }
+    for idx in 0..81 {
+        let (left, top) = (x(idx % 9), x(idx / 9));
+        if let Some(digit) = board.digit(idx) {
+            let ink = if options.is_given(board, idx) {
+                BLACK
+            } else {
+                colour(render::PLAYER_DIGIT)
+            };
+            canvas.digit(left + cell / 2.0, top + cell / 2.0, cell * 0.55, digit, ink);
+            continue;
+        }
+        if !options.show_pencilmarks {
+            continue;
+        }
+        for digit in board.pencilmarks(idx).iter().cloned() {
+            let cx = left + cell * (((digit - 1) % 3) as f64 + 0.5) / 3.0;
+            let cy = top + cell * (((digit - 1) / 3) as f64 + 0.5) / 3.0;
+            if options.struck_candidates.contains(&(idx, digit)) {
+                canvas.fill_circle(cx, cy, cell / 7.0, colour(render::STRUCK_CANDIDATE));
+            } else if options.highlighted_candidates.contains(&(idx, digit)) {
+                canvas.fill_circle(cx, cy, cell / 7.0, colour(render::HIGHLIGHT_CANDIDATE));
+            }
+            canvas.digit(cx, cy, cell * 0.2, digit, colour(render::PENCILMARK));
+        }
+    }
+    // Thin lines first so the box borders draw over them where they cross.
+    for thick in [false, true] {
+        for line in 0..10 {
+            if (line % 3 == 0) != thick {
+                continue;
+            }
+            let (width, ink) = if thick {
+                ((cell / 16.0).max(2.0), BLACK)
+            } else {
+                ((cell / 50.0).max(1.0), GREY)
+            };
+            let at = x(line) - width / 2.0;
+            let (start, length) = (margin - width / 2.0, 9.0 * cell + width);
+            canvas.fill_rect(at, start, width, length, ink);
+            canvas.fill_rect(start, at, length, width, ink);
+        }
+    }
+    return canvas;
+}
+
+pub fn to_png(board: &Board, options: &RenderOptions) -> Vec<u8> {
+    let canvas = rasterize(board, options);
+    return png::encode(canvas.width, canvas.height, &canvas.pixels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PencilmarkPolicy;
+    use crate::grade::Grader;
+
+    #[test]
+    fn test_rasterize() {
+        let mut puzzle = crate::catalog::test_board("nyt-easy");
+        puzzle.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        let step = Grader::new(&puzzle).next_step().unwrap();
+        let options = RenderOptions::new().with_cell_size(40).with_step(&step);
+        let canvas = rasterize(&puzzle, &options);
+        assert_eq!((canvas.width, canvas.height), (368, 368));
+        // the corner of the grid is on the thick border, and the first step's cell is lit
+        assert_eq!(canvas.pixel(4, 4), BLACK);
+        let idx = step.pattern[0];
+        let (left, top) = (4 + 40 * (idx % 9) as u32, 4 + 40 * (idx / 9) as u32);
+        assert_eq!(
+            canvas.pixel(left + 3, top + 3),
+            colour(render::HIGHLIGHT_CELL)
+        );
+        // the given 4 in the top left cell is drawn in black around its centre
+        let inked = (10..38)
+            .flat_map(|y| (10..38).map(move |x| (x, y)))
+            .filter(|(x, y)| canvas.pixel(*x, *y) == BLACK)
+            .count();
+        assert!(inked > 40);
+
+        let png = to_png(&puzzle, &options);
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G']));
+    }
+}
@@ -0,0 +1,462 @@
+use crate::board::Board;
+use crate::jpeg;
+use crate::png;
+use crate::raster::Canvas;
+use serde::Serialize;
+
+// Reads a puzzle off a screenshot or a photo of a printed grid. The image is thresholded
+// against its local average, so uneven lighting doesn't matter much, and the grid is whichever
+// big connected run of ink has its inner lines where a grid's should be. Its four outermost
+// corners give a perspective warp to a square, which is cut into 81 cells. In each cell the
+// largest blob of ink through the middle is the digit, matched against templates of common
+// newspaper and app fonts plus the font `raster` draws with. A photo needs the whole grid in
+// frame, roughly upright, with a little paper showing round it.
+
+// The templates: a "<font> <digit>" line, then 16 rows of 16 hex ink levels.
+const TEMPLATES: &str = include_str!("../data/digits.txt");
+const TEMPLATE_SIZE: usize = 16;
+// Pixels to a side of a cell once the grid is warped square.
+const CELL: usize = 48;
+
+#[derive(Serialize)]
+pub struct Recognized {
+    pub board: Board,
+    // How sure the classifier is of each cell, from 0 to 1, empty cells included.
+    pub confidence: Vec<f64>,
+}
+
+impl Recognized {
+    // The cells worth a second look before trusting the board.
+    pub fn doubtful(&self, threshold: f64) -> Vec<usize> {
+        return (0..81)
+            .filter(|idx| self.confidence[*idx] < threshold)
+            .collect();
+    }
+}
+
+// Luminance, 0 to 255.
+struct Grey {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Grey {
+    // Big photos are box-averaged down first; a grid doesn't need more than this many pixels.
+    fn from_canvas(canvas: &Canvas, max_side: usize) -> Grey {
+        let (width, height) = (canvas.width as usize, canvas.height as usize);
+        let factor = width.max(height).div_ceil(max_side).max(1);
+        let (out_width, out_height) = (width / factor, height / factor);
+        let mut values = vec![0.0; out_width * out_height];
+        for (i, value) in values.iter_mut().enumerate() {
+            let (x, y) = (i % out_width * factor, i / out_width * factor);
+            let mut sum = 0.0;
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    let [r, g, b] = canvas.pixels[(y + dy) * width + x + dx];
+                    sum += 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+                }
+            }
+            *value = sum / (factor * factor) as f64;
+        }
+        return Grey {
+            width: out_width,
+            height: out_height,
+            values,
+        };
+    }
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let x = x.clamp(0.0, (self.width - 1) as f64);
+        let y = y.clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let at = |x: usize, y: usize| self.values[y * self.width + x];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        return top * (1.0 - fy) + bottom * fy;
+    }
+    // Ink is anything darker than the average around it by more than `offset`.
+    fn threshold(&self, radius: usize, offset: f64) -> Vec<bool> {
+        let (width, height) = (self.width, self.height);
+        let mut sums = vec![0.0; (width + 1) * (height + 1)];
+        for y in 0..height {
+            for x in 0..width {
+                sums[(y + 1) * (width + 1) + x + 1] = self.values[y * width + x]
+                    + sums[y * (width + 1) + x + 1]
+                    + sums[(y + 1) * (width + 1) + x]
+                    - sums[y * (width + 1) + x];
+            }
+        }
+        let mut ink = vec![false; width * height];
+        for y in 0..height {
+            let (top, bottom) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+                let sum = sums[bottom * (width + 1) + right] - sums[top * (width + 1) + right]
+                    + sums[top * (width + 1) + left]
+                    - sums[bottom * (width + 1) + left];
+                let mean = sum / ((bottom - top) * (right - left)) as f64;
+                ink[y * width + x] = self.values[y * width + x] < mean - offset;
+            }
+        }
+        return ink;
+    }
+}
+
+// Eight-connected runs of ink, as lists of pixel indices.
+fn blobs(ink: &[bool], width: usize) -> Vec<Vec<usize>> {
+    let height = ink.len() / width;
+    let mut seen = vec![false; ink.len()];
+    let mut blobs = Vec::<Vec<usize>>::new();
+    for start in 0..ink.len() {
+        if !ink[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut blob = vec![start];
+        let mut next = 0;
+        while next < blob.len() {
+            let (x, y) = (blob[next] % width, blob[next] / width);
+            next += 1;
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let idx = ny * width + nx;
+                    if ink[idx] && !seen[idx] {
+                        seen[idx] = true;
+                        blob.push(idx);
+                    }
+                }
+            }
+        }
+        blobs.push(blob);
+    }
+    return blobs;
+}
+
+// Left, top, right and bottom, inclusive.
+fn bounds(blob: &[usize], width: usize) -> (usize, usize, usize, usize) {
+    let xs = blob.iter().map(|idx| idx % width);
+    let ys = blob.iter().map(|idx| idx / width);
+    return (
+        xs.clone().min().unwrap(),
+        ys.clone().min().unwrap(),
+        xs.max().unwrap(),
+        ys.max().unwrap(),
+    );
+}
+
+// The perspective transform taking the unit square onto a quadrilateral, corners given
+// clockwise from the top left (Heckbert's square-to-quad mapping).
+struct Homography([f64; 8]);
+
+impl Homography {
+    fn new(corners: [(f64, f64); 4]) -> Result<Homography, String> {
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = corners;
+        let (sx, sy) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+        let (dx1, dx2, dy1, dy2) = (x1 - x2, x3 - x2, y1 - y2, y3 - y2);
+        let den = dx1 * dy2 - dx2 * dy1;
+        if den.abs() < 1e-9 {
+            return Err("the grid's corners don't make a quadrilateral".to_string());
+        }
+        let g = (sx * dy2 - dx2 * sy) / den;
+        let h = (dx1 * sy - sx * dy1) / den;
+        return Ok(Homography([
+            x1 - x0 + g * x1,
+            x3 - x0 + h * x3,
+            x0,
+            y1 - y0 + g * y1,
+            y3 - y0 + h * y3,
+            y0,
+            g,
+            h,
+        ]));
+    }
+    fn map(&self, u: f64, v: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f, g, h] = self.0;
+        let w = g * u + h * v + 1.0;
+        return ((a * u + b * v + c) / w, (d * u + e * v + f) / w);
+    }
+}
+
+// The outermost corners of a blob, clockwise from the top left: the ends of the diagonals x + y
+// and x - y.
+fn corners(blob: &[usize], width: usize) -> [(f64, f64); 4] {
+    let extreme = |key: &dyn Fn((f64, f64)) -> f64| {
+        return blob
+            .iter()
+            .map(|idx| ((idx % width) as f64, (idx / width) as f64))
+            .max_by(|a, b| key(*a).partial_cmp(&key(*b)).unwrap())
+            .unwrap();
+    };
+    return [
+        extreme(&|(x, y)| -x - y),
+        extreme(&|(x, y)| x - y),
+        extreme(&|(x, y)| x + y),
+        extreme(&|(x, y)| y - x),
+    ];
+}
+
+// How much of the eight inner lines each way a grid in this quadrilateral would have falls on
+// ink, from 0 to 1. A real grid scores near 1; the shadow round the edge of a page doesn't.
+fn line_score(ink: &[bool], width: usize, homography: &Homography) -> f64 {
+    const SAMPLES: usize = 36;
+    let height = ink.len() / width;
+    let near_ink = |(x, y): (f64, f64)| {
+        let (x, y) = (x.round() as i64, y.round() as i64);
+        return (y - 1..y + 2).any(|y| {
+            (x - 1..x + 2).any(|x| {
+                x >= 0
+                    && y >= 0
+                    && (x as usize) < width
+                    && (y as usize) < height
+                    && ink[y as usize * width + x as usize]
+            })
+        });
+    };
+    let mut hits = 0;
+    for line in 1..9 {
+        let at = line as f64 / 9.0;
+        for sample in 0..SAMPLES {
+            let along = (sample as f64 + 0.5) / SAMPLES as f64;
+            hits += near_ink(homography.map(at, along)) as usize;
+            hits += near_ink(homography.map(along, at)) as usize;
+        }
+    }
+    return hits as f64 / (2 * 8 * SAMPLES) as f64;
+}
+
+// The grid as a square image, `CELL` pixels to a cell. It's one of the biggest blobs of ink,
+// whichever looks most like a grid.
+fn find_grid(grey: &Grey) -> Result<Grey, String> {
+    let radius = (grey.width.max(grey.height) / 40).max(4);
+    let ink = grey.threshold(radius, 10.0);
+    let mut candidates = blobs(&ink, grey.width);
+    candidates.sort_by_key(|blob| std::cmp::Reverse(blob.len()));
+    let mut best: Option<(f64, Homography)> = None;
+    for blob in candidates.iter().take(5) {
+        let (left, top, right, bottom) = bounds(blob, grey.width);
+        if (right - left).min(bottom - top) < grey.width.min(grey.height) / 4 {
+            continue;
+        }
+        let homography = match Homography::new(corners(blob, grey.width)) {
+            Ok(homography) => homography,
+            Err(_) => continue,
+        };
+        let score = line_score(&ink, grey.width, &homography);
+        if best.as_ref().is_none_or(|(best, _)| score > *best) {
+            best = Some((score, homography));
+        }
+    }
+    let homography = match best {
+        Some((score, homography)) if score > 0.6 => homography,
+        _ => return Err("couldn't find a grid in the image".to_string()),
+    };
+
+    let side = 9 * CELL;
+    let mut values = vec![0.0; side * side];
+    for (i, value) in values.iter_mut().enumerate() {
+        let u = ((i % side) as f64 + 0.5) / side as f64;
+        let v = ((i / side) as f64 + 0.5) / side as f64;
+        let (x, y) = homography.map(u, v);
+        *value = grey.sample(x, y);
+    }
+    return Ok(Grey {
+        width: side,
+        height: side,
+        values,
+    });
+}
+
+// A blob scaled to fit `TEMPLATE_SIZE` square, keeping its shape, as ink levels from 0 to 1.
+fn normalize(blob: &[usize], width: usize) -> Vec<f64> {
+    const SAMPLES: usize = 4;
+    let (left, top, right, bottom) = bounds(blob, width);
+    let (blob_width, blob_height) = (right - left + 1, bottom - top + 1);
+    let mut mask = vec![false; blob_width * blob_height];
+    for idx in blob {
+        mask[(idx / width - top) * blob_width + idx % width - left] = true;
+    }
+    let side = blob_width.max(blob_height) as f64;
+    let offset_x = (side - blob_width as f64) / 2.0;
+    let offset_y = (side - blob_height as f64) / 2.0;
+    let step = |at: usize, sub: usize| at as f64 + (sub as f64 + 0.5) / SAMPLES as f64;
+    let mut levels = vec![0.0; TEMPLATE_SIZE * TEMPLATE_SIZE];
+    for (i, level) in levels.iter_mut().enumerate() {
+        let (col, row) = (i % TEMPLATE_SIZE, i / TEMPLATE_SIZE);
+        let mut inked = 0;
+        for sub_y in 0..SAMPLES {
+            for sub_x in 0..SAMPLES {
+                let x = step(col, sub_x) * side / TEMPLATE_SIZE as f64 - offset_x;
+                let y = step(row, sub_y) * side / TEMPLATE_SIZE as f64 - offset_y;
+                if x >= 0.0
+                    && y >= 0.0
+                    && (x as usize) < blob_width
+                    && (y as usize) < blob_height
+                    && mask[y as usize * blob_width + x as usize]
+                {
+                    inked += 1;
+                }
+            }
+        }
+        *level = inked as f64 / (SAMPLES * SAMPLES) as f64;
+    }
+    return levels;
+}
+
+// Centred and scaled to unit length, so a dot product is the correlation.
+fn standardize(levels: &[f64]) -> Vec<f64> {
+    let mean = levels.iter().sum::<f64>() / levels.len() as f64;
+    let centred: Vec<f64> = levels.iter().map(|level| level - mean).collect();
+    let norm = centred.iter().map(|c| c * c).sum::<f64>().sqrt().max(1e-9);
+    return centred.iter().map(|c| c / norm).collect();
+}
+
+fn templates() -> Vec<(u8, Vec<f64>)> {
+    let mut templates = Vec::<(u8, Vec<f64>)>::new();
+    let mut lines = TEMPLATES.lines();
+    while let Some(header) = lines.next() {
+        let digit = header.rsplit(' ').next().unwrap().parse::<u8>().unwrap();
+        let levels: Vec<f64> = lines
+            .by_ref()
+            .take(TEMPLATE_SIZE)
+            .flat_map(|row| row.chars())
+            .map(|level| level.to_digit(16).unwrap() as f64 / 15.0)
+            .collect();
+        templates.push((digit, standardize(&levels)));
+    }
+    // The bitmap font boards are rendered with here, drawn big enough to be exact.
+    for digit in 1..10 {
+        let mut canvas = Canvas::new(60, 80, [255, 255, 255]);
+        canvas.digit(30.0, 40.0, 70.0, digit, [0, 0, 0]);
+        let blob: Vec<usize> = (0..canvas.pixels.len())
+            .filter(|idx| canvas.pixels[*idx] == [0, 0, 0])
+            .collect();
+        templates.push((digit, standardize(&normalize(&blob, 60))));
+    }
+    return templates;
+}
+
+// The digit in one cell of the warped grid, if there is one, and how sure that is.
+fn classify(
+    grid: &Grey,
+    ink: &[bool],
+    idx: usize,
+    templates: &[(u8, Vec<f64>)],
+) -> (Option<u8>, f64) {
+    // The cell less a margin, which drops most of the grid lines around it.
+    let margin = CELL / 8;
+    let inner = CELL - 2 * margin;
+    let (left, top) = (idx % 9 * CELL + margin, idx / 9 * CELL + margin);
+    let mut cell = vec![false; inner * inner];
+    for (i, pixel) in cell.iter_mut().enumerate() {
+        *pixel = ink[(top + i / inner) * grid.width + left + i % inner];
+    }
+    // Digits sit in the middle of their cells; what's left of the grid lines hugs the edges.
+    let (middle_start, middle_end) = (inner / 3, inner - inner / 3);
+    let in_middle = |i: &usize| {
+        (middle_start..middle_end).contains(&(i % inner))
+            && (middle_start..middle_end).contains(&(i / inner))
+    };
+    let digit = blobs(&cell, inner)
+        .into_iter()
+        .filter(|blob| {
+            let (_, top, _, bottom) = bounds(blob, inner);
+            return blob.iter().any(in_middle) && bottom - top + 1 >= CELL * 7 / 20;
+        })
+        .max_by_key(|blob| blob.len());
+    let blob = match digit {
+        Some(blob) => blob,
+        None => {
+            // An empty cell is less certain the more ink there is in the middle of it.
+            let inked = (0..cell.len()).filter(|i| cell[*i] && in_middle(i)).count();
+            let middle = (middle_end - middle_start) * (middle_end - middle_start);
+            return (
+                None,
+                (1.0 - 5.0 * inked as f64 / middle as f64).clamp(0.0, 1.0),
+            );
+        }
+    };
+
+    let levels = standardize(&normalize(&blob, inner));
+    let mut scores = [f64::MIN; 10];
+    for (digit, template) in templates {
+        let score: f64 = levels.iter().zip(template).map(|(a, b)| a * b).sum();
+        scores[*digit as usize] = scores[*digit as usize].max(score);
+    }
+    let mut ranked: Vec<usize> = (1..10).collect();
+    ranked.sort_by(|a, b| scores[*b].partial_cmp(&scores[*a]).unwrap());
+    let (best, runner_up) = (scores[ranked[0]], scores[ranked[1]]);
+    let confidence = best.max(0.0) * ((best - runner_up) / 0.2).clamp(0.0, 1.0);
+    return (Some(ranked[0] as u8), confidence);
+}
+
+pub fn recognize_image(canvas: &Canvas) -> Result<Recognized, String> {
+    let grey = Grey::from_canvas(canvas, 1000);
+    if grey.width == 0 || grey.height == 0 {
+        return Err("the image is too narrow to hold a grid".to_string());
+    }
+    let grid = find_grid(&grey)?;
+    let ink = grid.threshold(CELL / 4, 10.0);
+    let templates = templates();
+    let mut digits = [None; 81];
+    let mut confidence = vec![0.0; 81];
+    for idx in 0..81 {
+        let (digit, sure) = classify(&grid, &ink, idx, &templates);
+        digits[idx] = digit;
+        confidence[idx] = sure;
+    }
+    return Ok(Recognized {
+        board: Board::from_digits(digits),
+        confidence,
+    });
+}
+
+// A PNG or JPEG, told apart by its first bytes.
+pub fn recognize(bytes: &[u8]) -> Result<Recognized, String> {
+    let canvas = if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        png::decode(bytes)?
+    } else if bytes.starts_with(&[0xff, 0xd8]) {
+        jpeg::decode(bytes)?
+    } else {
+        return Err("not a PNG or JPEG image".to_string());
+    };
+    return recognize_image(&canvas);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::candidates;
+    use crate::raster;
+    use crate::render::RenderOptions;
+
+    #[test]
+    fn test_recognize_render() {
+        let puzzle = crate::catalog::test_board("nyt-hard");
+        let mut options = RenderOptions::new().with_cell_size(40);
+        options.show_pencilmarks = false;
+        let recognized = recognize(&raster::to_png(&puzzle, &options)).unwrap();
+        assert_eq!(
+            candidates::format_line(&recognized.board),
+            candidates::format_line(&puzzle)
+        );
+        assert!(recognized.doubtful(0.5).is_empty());
+        assert!(recognize(b"not an image").is_err());
+        let blank = Canvas::new(100, 100, [255, 255, 255]);
+        assert!(recognize_image(&blank).is_err());
+        // scaled down to fit, this is less than a pixel wide
+        let sliver = png::encode(1, 3000, &vec![[255, 255, 255]; 3000]);
+        assert!(recognize(&sliver).is_err());
+    }
+
+    #[test]
+    fn test_recognize_photo() {
+        // A grid typeset in DejaVu Sans, photographed at an angle under uneven light
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data/images/photo.jpg");
+        let recognized = recognize(&std::fs::read(path).unwrap()).unwrap();
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        assert_eq!(recognized.board.to_line(), puzzle.to_line());
+        assert!(recognized.doubtful(0.5).is_empty());
+    }
+}
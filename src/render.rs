@@ -0,0 +1,202 @@
+use crate::board::Board;
+use crate::grade::Step;
+
+// Pictures of a board. The options say what to draw besides the digits: which digits were
+// given, which cells and candidates to highlight for a hint, and how big to make it.
+
+pub struct RenderOptions {
+    // Side of one cell, in pixels.
+    pub cell_size: u32,
+    // Digits placed in this board are drawn as givens, the rest as the player's. Without it
+    // every digit is drawn as a given.
+    pub givens: Option<Board>,
+    pub show_pencilmarks: bool,
+    pub highlighted_cells: Vec<usize>,
+    // Candidates to draw attention to, such as the ones forming a pattern.
+    pub highlighted_candidates: Vec<(usize, u8)>,
+    // Candidates a step removes.
+    pub struck_candidates: Vec<(usize, u8)>,
+}
+
+impl RenderOptions {
+    pub fn new() -> RenderOptions {
+        return RenderOptions {
+            cell_size: 50,
+            givens: None,
+            show_pencilmarks: true,
+            highlighted_cells: vec![],
+            highlighted_candidates: vec![],
+            struck_candidates: vec![],
+        };
+    }
+    pub fn with_givens(mut self, givens: Board) -> RenderOptions {
+        self.givens = Some(givens);
+        return self;
+    }
+    pub fn with_cell_size(mut self, cell_size: u32) -> RenderOptions {
+        self.cell_size = cell_size;
+        return self;
+    }
+    // Shows a grader step as a hint: the pattern's cells lit up, the digits it places marked
+    // as candidates to keep, and the candidates it eliminates struck.
+    pub fn with_step(mut self, step: &Step) -> RenderOptions {
+        self.highlighted_cells.extend(step.pattern.iter().cloned());
+        self.highlighted_candidates
+            .extend(step.placements.iter().cloned());
+        self.struck_candidates
+            .extend(step.eliminations.iter().cloned());
+        return self;
+    }
+    pub(crate) fn is_given(&self, board: &Board, idx: usize) -> bool {
+        return match &self.givens {
+            Some(givens) => givens.digit(idx).is_some(),
+            None => board.digit(idx).is_some(),
+        };
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        return RenderOptions::new();
+    }
+}
+
+pub(crate) const HIGHLIGHT_CELL: &str = "#fff3b0";
+pub(crate) const HIGHLIGHT_CANDIDATE: &str = "#8fd694";
+pub(crate) const STRUCK_CANDIDATE: &str = "#f28b82";
+pub(crate) const PLAYER_DIGIT: &str = "#2b5fd9";
+pub(crate) const PENCILMARK: &str = "#666666";
+
+pub fn to_svg(board: &Board, options: &RenderOptions) -> String {
+    let cell = options.cell_size as f64;
+    let margin = (cell / 10.0).max(2.0);
+    let side = 9.0 * cell + 2.0 * margin;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{s}\" height=\"{s}\" \
+         viewBox=\"0 0 {s} {s}\">\n",
+        s = side
+    );
+    svg.push_str(&format!(
+        "<rect width=\"{s}\" height=\"{s}\" fill=\"#ffffff\"/>\n",
+        s = side
+    ));
+    let x = |col: usize| margin + cell * col as f64;
+
+    for idx in &options.highlighted_cells {
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{c}\" height=\"{c}\" fill=\"{}\"/>\n",
+            x(idx % 9),
+            x(idx / 9),
+            HIGHLIGHT_CELL,
+            c = cell
+        ));
+    }
+
+    let font = "font-family=\"Helvetica, Arial, sans-serif\" text-anchor=\"middle\" \
+                dominant-baseline=\"central\"";
+    for idx in 0..81 {
+        let (left, top) = (x(idx % 9), x(idx / 9));
+        if let Some(digit) = board.digit(idx) {
+            let (weight, colour) = if options.is_given(board, idx) {
+                ("bold", "#000000")
+            } else {
+                ("normal", PLAYER_DIGIT)
+            };
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-weight=\"{}\" fill=\"{}\" {}>{}</text>\n",
+                left + cell / 2.0,
+                top + cell / 2.0,
+                cell * 0.6,
+                weight,
+                colour,
+                font,
+                digit
+            ));
+            continue;
+        }
+        if !options.show_pencilmarks {
+            continue;
+        }
+        let mut marks: Vec<u8> = board.pencilmarks(idx).iter().cloned().collect();
+        marks.sort_unstable();
+        for digit in marks {
+            let cx = left + cell * (((digit - 1) % 3) as f64 + 0.5) / 3.0;
+            let cy = top + cell * (((digit - 1) / 3) as f64 + 0.5) / 3.0;
+            let ring = if options.struck_candidates.contains(&(idx, digit)) {
+                Some(STRUCK_CANDIDATE)
+            } else if options.highlighted_candidates.contains(&(idx, digit)) {
+                Some(HIGHLIGHT_CANDIDATE)
+            } else {
+                None
+            };
+            if let Some(ring) = ring {
+                svg.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+                    cx,
+                    cy,
+                    cell / 7.0,
+                    ring
+                ));
+            }
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\" {}>{}</text>\n",
+                cx,
+                cy,
+                cell * 0.22,
+                PENCILMARK,
+                font,
+                digit
+            ));
+        }
+    }
+
+    for line in 0..10 {
+        let (width, colour) = if line % 3 == 0 {
+            (cell / 16.0, "#000000")
+        } else {
+            (cell / 50.0, "#999999")
+        };
+        let at = x(line);
+        let (start, end) = (margin, side - margin);
+        svg.push_str(&format!(
+            "<line x1=\"{a}\" y1=\"{s}\" x2=\"{a}\" y2=\"{e}\" stroke=\"{c}\" stroke-width=\"{w}\" stroke-linecap=\"square\"/>\n\
+             <line x1=\"{s}\" y1=\"{a}\" x2=\"{e}\" y2=\"{a}\" stroke=\"{c}\" stroke-width=\"{w}\" stroke-linecap=\"square\"/>\n",
+            a = at,
+            s = start,
+            e = end,
+            c = colour,
+            w = width
+        ));
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PencilmarkPolicy;
+    use crate::grade::Grader;
+
+    #[test]
+    fn test_svg() {
+        let puzzle = crate::catalog::test_board("nyt-easy");
+        let svg = to_svg(&puzzle, &RenderOptions::new().with_cell_size(40));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"368\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // 38 givens, all bold, and 20 grid lines
+        assert_eq!(svg.matches("font-weight=\"bold\"").count(), 38);
+        assert_eq!(svg.matches("<line ").count(), 20);
+
+        // a player's digit in cell 2, and a hint for the next step
+        let mut player = format!("{}3{}", &puzzle.to_line()[..2], &puzzle.to_line()[3..])
+            .parse::<Board>()
+            .unwrap();
+        player.apply_pencilmark_policy(PencilmarkPolicy::Recompute);
+        let step = Grader::new(&player).next_step().unwrap();
+        let options = RenderOptions::new().with_givens(puzzle).with_step(&step);
+        let svg = to_svg(&player, &options);
+        assert_eq!(svg.matches(PLAYER_DIGIT).count(), 1);
+        assert!(svg.contains(HIGHLIGHT_CELL));
+    }
+}
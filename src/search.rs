@@ -0,0 +1,279 @@
+use crate::board::Board;
+use crate::budget::{Budget, Meter, Outcome, StopReason};
+use crate::rng::Rng;
+
+// Exhaustive backtracking search over the digits of a board. Unlike the naturalistic solver in
+// `board`, this ignores pencilmarks entirely and is only concerned with what the true
+// completion(s) of the placed digits are.
+
+pub enum Solutions {
+    None,
+    Unique(Box<Board>),
+    Multiple,
+}
+
+fn house_box(idx: usize) -> usize {
+    return 3 * (idx / 27) + (idx % 9) / 3;
+}
+
+#[derive(Clone)]
+struct Grid {
+    cells: [u8; 81],
+    rows: [u16; 9],
+    cols: [u16; 9],
+    boxes: [u16; 9],
+}
+
+impl Grid {
+    // Returns None if the placed digits already conflict with each other.
+    fn from_board(board: &Board) -> Option<Grid> {
+        let mut grid = Grid {
+            cells: [0; 81],
+            rows: [0; 9],
+            cols: [0; 9],
+            boxes: [0; 9],
+        };
+        for idx in 0..81 {
+            if let Some(digit) = board.digit(idx) {
+                if !(1..=9).contains(&digit) || !grid.allows(idx, digit) {
+                    return None;
+                }
+                grid.set(idx, digit);
+            }
+        }
+        return Some(grid);
+    }
+    fn allows(&self, idx: usize, digit: u8) -> bool {
+        let bit = 1u16 << digit;
+        return (self.rows[idx / 9] | self.cols[idx % 9] | self.boxes[house_box(idx)]) & bit == 0;
+    }
+    fn candidates(&self, idx: usize) -> u16 {
+        let used = self.rows[idx / 9] | self.cols[idx % 9] | self.boxes[house_box(idx)];
+        return !used & 0b11_1111_1110;
+    }
+    fn set(&mut self, idx: usize, digit: u8) {
+        let bit = 1u16 << digit;
+        self.cells[idx] = digit;
+        self.rows[idx / 9] |= bit;
+        self.cols[idx % 9] |= bit;
+        self.boxes[house_box(idx)] |= bit;
+    }
+    fn unset(&mut self, idx: usize) {
+        let bit = !(1u16 << self.cells[idx]);
+        self.cells[idx] = 0;
+        self.rows[idx / 9] &= bit;
+        self.cols[idx % 9] &= bit;
+        self.boxes[house_box(idx)] &= bit;
+    }
+    // The empty cell with the fewest candidates, or None if the grid is full.
+    fn most_constrained(&self) -> Option<(usize, u16)> {
+        let mut best: Option<(usize, u16)> = None;
+        for idx in 0..81 {
+            if self.cells[idx] != 0 {
+                continue;
+            }
+            let candidates = self.candidates(idx);
+            let better = match best {
+                None => true,
+                Some((_, current)) => candidates.count_ones() < current.count_ones(),
+            };
+            if better {
+                best = Some((idx, candidates));
+                if candidates.count_ones() <= 1 {
+                    break;
+                }
+            }
+        }
+        return best;
+    }
+    fn to_board(&self) -> Board {
+        let mut digits = [None; 81];
+        for (digit, cell) in digits.iter_mut().zip(self.cells.iter()) {
+            *digit = Some(*cell);
+        }
+        return Board::from_digits(digits);
+    }
+}
+
+// Depth first search, collecting up to `limit` solutions into `found`. Stops early with the
+// reason if the budget runs out.
+fn search(
+    grid: &mut Grid,
+    limit: usize,
+    found: &mut Vec<Grid>,
+    meter: &mut Meter,
+) -> Option<StopReason> {
+    let (idx, candidates) = match grid.most_constrained() {
+        None => {
+            found.push(grid.clone());
+            return None;
+        }
+        Some(cell) => cell,
+    };
+    for digit in 1..10u8 {
+        if candidates & (1 << digit) == 0 {
+            continue;
+        }
+        if let Some(reason) = meter.node() {
+            return Some(reason);
+        }
+        grid.set(idx, digit);
+        let stopped = search(grid, limit, found, meter);
+        grid.unset(idx);
+        if stopped.is_some() || found.len() >= limit {
+            return stopped;
+        }
+    }
+    return None;
+}
+
+// Fill the grid with the first completion found, trying digits in a random order.
+fn fill_random(grid: &mut Grid, rng: &mut Rng) -> bool {
+    let (idx, candidates) = match grid.most_constrained() {
+        None => return true,
+        Some(cell) => cell,
+    };
+    let mut digits = [1u8, 2, 3, 4, 5, 6, 7, 8, 9];
+    rng.shuffle(&mut digits);
+    for digit in digits.iter() {
+        if candidates & (1 << digit) == 0 {
+            continue;
+        }
+        grid.set(idx, *digit);
+        if fill_random(grid, rng) {
+            return true;
+        }
+        grid.unset(idx);
+    }
+    return false;
+}
+
+// A random completion of the board's digits, or None if it has none. Starting from an empty
+// board this gives a random full grid.
+pub fn random_solution(board: &Board, rng: &mut Rng) -> Option<Board> {
+    let mut grid = Grid::from_board(board)?;
+    if fill_random(&mut grid, rng) {
+        return Some(grid.to_board());
+    }
+    return None;
+}
+
+// Up to `limit` completions of the board's digits. If the budget runs out first, the result
+// holds whatever had been found by then.
+pub fn solutions_within(board: &Board, limit: usize, budget: &Budget) -> Outcome<Vec<Board>> {
+    let mut meter = Meter::new(budget);
+    let (solutions, stopped) = solutions_metered(board, limit, &mut meter);
+    return meter.finish(solutions, stopped);
+}
+
+// `solutions_within` charging an existing meter, so several searches can share one budget.
+pub fn solutions_metered(
+    board: &Board,
+    limit: usize,
+    meter: &mut Meter,
+) -> (Vec<Board>, Option<StopReason>) {
+    let mut grid = match Grid::from_board(board) {
+        Some(grid) => grid,
+        None => return (vec![], None),
+    };
+    let mut found = Vec::<Grid>::new();
+    let mut stopped = None;
+    if limit > 0 {
+        stopped = search(&mut grid, limit, &mut found, meter);
+    }
+    return (found.iter().map(|grid| grid.to_board()).collect(), stopped);
+}
+
+// Count completions of the board's digits, stopping once `limit` have been found.
+pub fn count_solutions(board: &Board, limit: usize) -> usize {
+    return count_solutions_within(board, limit, &Budget::unlimited()).result;
+}
+
+// `count_solutions` within a budget. The completions themselves are never turned into boards,
+// so large limits stay cheap.
+pub fn count_solutions_within(board: &Board, limit: usize, budget: &Budget) -> Outcome<usize> {
+    let mut meter = Meter::new(budget);
+    let mut grid = match Grid::from_board(board) {
+        Some(grid) => grid,
+        None => return meter.finish(0, None),
+    };
+    let mut found = Vec::<Grid>::new();
+    let mut stopped = None;
+    if limit > 0 {
+        stopped = search(&mut grid, limit, &mut found, &mut meter);
+    }
+    return meter.finish(found.len(), stopped);
+}
+
+// Whether the board has none, one or several completions. Only trustworthy if the outcome is
+// complete; a search cut short reports what it had found so far.
+pub fn unique_solution_within(board: &Board, budget: &Budget) -> Outcome<Solutions> {
+    let outcome = solutions_within(board, 2, budget);
+    let mut found = outcome.result;
+    let solutions = match found.len() {
+        0 => Solutions::None,
+        1 => Solutions::Unique(Box::new(found.remove(0))),
+        _ => Solutions::Multiple,
+    };
+    return Outcome {
+        result: solutions,
+        stopped: outcome.stopped,
+        steps: outcome.steps,
+        nodes: outcome.nodes,
+    };
+}
+
+pub fn unique_solution(board: &Board) -> Solutions {
+    return unique_solution_within(board, &Budget::unlimited()).result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_nyt_boards_are_unique() {
+        for id in ["nyt-easy", "nyt-hard"] {
+            let board = crate::catalog::test_board(id);
+            match unique_solution(&board) {
+                Solutions::Unique(solution) => {
+                    for idx in 0..81 {
+                        if let Some(digit) = board.digit(idx) {
+                            assert_eq!(solution.digit(idx), Some(digit));
+                        }
+                    }
+                    assert_eq!(count_solutions(&solution, 2), 1);
+                }
+                _ => panic!("expected a unique solution"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_budget() {
+        let empty = Board::new(HashMap::new());
+        let outcome = solutions_within(&empty, 1000, &Budget::unlimited().with_max_nodes(100));
+        assert_eq!(outcome.stopped, StopReason::NodeLimit);
+        assert_eq!(outcome.nodes, 100);
+
+        let cancel = crate::budget::CancelToken::new();
+        cancel.cancel();
+        let outcome = solutions_within(&empty, 1000, &Budget::unlimited().with_cancel(cancel));
+        assert_eq!(outcome.stopped, StopReason::Cancelled);
+        assert!(outcome.nodes <= 256);
+
+        let puzzle = crate::catalog::test_board("nyt-hard");
+        let outcome = unique_solution_within(&puzzle, &Budget::unlimited().with_max_nodes(100_000));
+        assert!(outcome.is_complete());
+    }
+
+    #[test]
+    fn test_empty_and_conflicting_boards() {
+        assert_eq!(count_solutions(&Board::new(HashMap::new()), 2), 2);
+        let mut conflict = HashMap::new();
+        conflict.insert(0, 5);
+        conflict.insert(8, 5);
+        assert_eq!(count_solutions(&Board::new(conflict), 2), 0);
+    }
+}
@@ -1,30 +1,369 @@
 use crate::board;
+use crate::budget::Budget;
+use crate::candidates;
+use crate::catalog;
+use crate::check;
+use crate::daily;
+use crate::envelope;
+use crate::fpuzzles;
+use crate::generate;
+use crate::grade;
+use crate::latex;
+use crate::minimal;
+use crate::raster;
+use crate::recognize;
+use crate::render;
+use crate::rng::Rng;
+use crate::transform::Transform;
+use hyper::body::HttpBody;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
-async fn hello_world(_req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    let res = Response::builder()
+// Every solve the server runs gets a deadline, so one bad board can't hold a worker forever.
+// Requests can ask for less time than this but never more.
+const MAX_SOLVE_MILLIS: u64 = 2000;
+
+// Every grader step places a digit or clears a candidate, so no board has more steps than this.
+const MAX_HINT: u64 = 81 + 729;
+
+// The largest request body read, which leaves room for a phone photo of a puzzle.
+const MAX_BODY_BYTES: usize = 4 << 20;
+
+// Reads the body a chunk at a time, giving up as soon as it runs past `MAX_BODY_BYTES`.
+async fn read_body(req: Request<Body>) -> Result<Vec<u8>, Response<Body>> {
+    let mut body = req.into_body();
+    let mut bytes = Vec::<u8>::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| bad_request(err.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(
+                    format!("request bodies are limited to {} bytes", MAX_BODY_BYTES).into(),
+                )
+                .unwrap());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    return Ok(bytes);
+}
+
+async fn stringify_body(req: Request<Body>) -> Result<String, Response<Body>> {
+    let body_bytes = read_body(req).await?;
+    return String::from_utf8(body_bytes)
+        .map_err(|_| bad_request("request body isn't UTF-8".to_string()));
+}
+
+// Query strings are either a bare box index, which is what the frontend sends, or `key=value`
+// pairs: `box=<0-8>`, `pencilmarks=<trust|recompute|intersect>` and
+// `schedule=<round_robin|most_filled|most_recent|global>`, plus the budget limits `steps=`,
+// `nodes=` and `timeout_ms=`. Puzzle endpoints also take `date=<YYYY-MM-DD>`,
+// `difficulty=<easy|medium|hard|expert|fiendish>` and `puzzle=<catalog ID>`, and the catalog
+// listing filters on `collection=`, `tag=` and `difficulty=`. Catalog puzzles can be disguised
+// with `shuffle=<seed>`, which serves a randomly transformed but equivalent puzzle. Rendering
+// takes `size=<pixels per cell>`, `cells=<idx,idx,...>` to highlight, and `hint=<n>` to show
+// the grader's nth step from the board.
+struct Query {
+    box_index: Option<u8>,
+    policy: board::PencilmarkPolicy,
+    schedule: board::BoxSchedule,
+    max_steps: Option<u64>,
+    max_nodes: Option<u64>,
+    timeout_ms: u64,
+    date: Option<daily::Date>,
+    difficulty: Option<grade::Difficulty>,
+    puzzle: Option<String>,
+    collection: Option<String>,
+    tag: Option<String>,
+    shuffle: Option<u64>,
+    cell_size: Option<u32>,
+    highlighted_cells: Vec<usize>,
+    hint: Option<u64>,
+}
+
+impl Query {
+    fn budget(&self) -> Budget {
+        let mut budget = Budget::unlimited().with_timeout(Duration::from_millis(self.timeout_ms));
+        budget.max_steps = self.max_steps;
+        budget.max_nodes = self.max_nodes;
+        return budget;
+    }
+    fn filter(&self) -> catalog::Filter {
+        return catalog::Filter {
+            collection: self.collection.clone(),
+            tag: self.tag.clone(),
+            difficulty: self.difficulty,
+        };
+    }
+}
+
+fn parse_limit(value: &str) -> Result<u64, String> {
+    return value
+        .parse::<u64>()
+        .map_err(|_| format!("not a valid limit: {}", value));
+}
+
+fn parse_box(value: &str) -> Result<u8, String> {
+    return match value.parse::<u8>() {
+        Ok(box_index) if box_index < 9 => Ok(box_index),
+        _ => Err(format!("not a box index: {}", value)),
+    };
+}
+
+fn parse_query(query: Option<&str>) -> Result<Query, String> {
+    let mut parsed = Query {
+        box_index: None,
+        policy: board::PencilmarkPolicy::default(),
+        schedule: board::BoxSchedule::default(),
+        max_steps: None,
+        max_nodes: None,
+        timeout_ms: MAX_SOLVE_MILLIS,
+        date: None,
+        difficulty: None,
+        puzzle: None,
+        collection: None,
+        tag: None,
+        shuffle: None,
+        cell_size: None,
+        highlighted_cells: vec![],
+        hint: None,
+    };
+    for pair in query.unwrap_or("").split('&') {
+        let mut parts = pair.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("box"), Some(value)) => parsed.box_index = Some(parse_box(value)?),
+            (Some("pencilmarks"), Some(value)) => parsed.policy = value.parse()?,
+            (Some("schedule"), Some(value)) => parsed.schedule = value.parse()?,
+            (Some("steps"), Some(value)) => parsed.max_steps = Some(parse_limit(value)?),
+            (Some("nodes"), Some(value)) => parsed.max_nodes = Some(parse_limit(value)?),
+            (Some("date"), Some(value)) => parsed.date = Some(value.parse()?),
+            (Some("difficulty"), Some(value)) => parsed.difficulty = Some(value.parse()?),
+            (Some("puzzle"), Some(value)) => parsed.puzzle = Some(value.to_string()),
+            (Some("collection"), Some(value)) => parsed.collection = Some(value.to_string()),
+            (Some("tag"), Some(value)) => parsed.tag = Some(value.to_string()),
+            (Some("shuffle"), Some(value)) => parsed.shuffle = Some(parse_limit(value)?),
+            (Some("size"), Some(value)) => {
+                parsed.cell_size = Some(parse_limit(value)?.clamp(10, 200) as u32)
+            }
+            (Some("hint"), Some(value)) => match parse_limit(value)? {
+                hint if hint <= MAX_HINT => parsed.hint = Some(hint.max(1)),
+                hint => return Err(format!("hints only go up to step {}: {}", MAX_HINT, hint)),
+            },
+            (Some("cells"), Some(value)) => {
+                for cell in value.split(',').filter(|cell| !cell.is_empty()) {
+                    match cell.parse::<usize>() {
+                        Ok(idx) if idx < 81 => parsed.highlighted_cells.push(idx),
+                        _ => return Err(format!("not a cell index: {}", cell)),
+                    }
+                }
+            }
+            (Some("timeout_ms"), Some(value)) => {
+                parsed.timeout_ms = parse_limit(value)?.min(MAX_SOLVE_MILLIS)
+            }
+            (Some(bare), None) if bare.parse::<u64>().is_ok() => {
+                parsed.box_index = Some(parse_box(bare)?)
+            }
+            _ => {}
+        }
+    }
+    return Ok(parsed);
+}
+
+#[derive(Serialize)]
+struct DailyPuzzle {
+    date: String,
+    difficulty: grade::Difficulty,
+    hardest: grade::Technique,
+    puzzle: board::Board,
+}
+
+// Daily puzzles are served for this many days either side of today.
+const DAILY_WINDOW_DAYS: i64 = 7;
+// Generating a daily puzzle gets a deadline like a solve, but a longer one: the hardest days take
+// a couple of seconds, and a generation that gives up is thrown away rather than cached.
+const MAX_GENERATE_MILLIS: u64 = 10_000;
+
+type DailyPuzzleCell = Arc<Mutex<Option<Result<generate::Generated, String>>>>;
+type DailyCache = Mutex<HashMap<(daily::Date, grade::Difficulty), DailyPuzzleCell>>;
+
+// The harder daily puzzles take a while to generate, and everyone asks for the same few. Each
+// date and difficulty gets one cell, so concurrent requests wait on the same generation, and
+// dates that drift out of the window are dropped. Only finished generations are kept; one that
+// ran out of time leaves the cell empty for the next request to try again.
+fn daily_cache() -> &'static DailyCache {
+    static CACHE: OnceLock<DailyCache> = OnceLock::new();
+    return CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+}
+
+async fn daily_puzzle(query: &Query) -> Result<(daily::Date, generate::Generated), Response<Body>> {
+    let today = daily::Date::today();
+    let in_window =
+        |date: &daily::Date| (date.to_days() - today.to_days()).abs() <= DAILY_WINDOW_DAYS;
+    let date = query.date.unwrap_or(today);
+    if !in_window(&date) {
+        return Err(bad_request(format!(
+            "daily puzzles are only served within {} days of today: {}",
+            DAILY_WINDOW_DAYS, date
+        )));
+    }
+    let difficulty = query.difficulty.unwrap_or(grade::Difficulty::Medium);
+    let cell = {
+        let mut cache = daily_cache().lock().unwrap();
+        cache.retain(|(date, _), _| in_window(date));
+        cache.entry((date, difficulty)).or_default().clone()
+    };
+    let generated = blocking(move || {
+        let mut cell = cell.lock().unwrap();
+        if let Some(generated) = &*cell {
+            return generated.clone();
+        }
+        let budget = Budget::unlimited().with_timeout(Duration::from_millis(MAX_GENERATE_MILLIS));
+        let generated = daily::daily_puzzle_within(date, difficulty, &budget);
+        let finished = !matches!(generated, Err(generate::GenerateError::Unfinished(_)));
+        let generated = generated.map_err(|err| err.to_string());
+        if finished {
+            *cell = Some(generated.clone());
+        }
+        return generated;
+    })
+    .await?;
+    return match generated {
+        Ok(generated) => Ok((date, generated)),
+        Err(err) => Err(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header("Access-Control-Allow-Origin", "*")
+            .body::<Body>(err.into())
+            .unwrap()),
+    };
+}
+
+static CATALOG: OnceLock<catalog::Catalog> = OnceLock::new();
+
+fn catalog() -> &'static catalog::Catalog {
+    return CATALOG.get().unwrap();
+}
+
+fn catalog_board(query: &Query, id: &str) -> Option<board::Board> {
+    let puzzle = catalog().get(id)?.board();
+    return match query.shuffle {
+        Some(seed) => Some(Transform::random(&mut Rng::new(seed)).apply(&puzzle)),
+        None => Some(puzzle),
+    };
+}
+
+// Boards can be posted in any of the forms `envelope` reads, with or without the envelope. A
+// board posted as `candidates` or `grid` comes back with any lone candidates placed.
+fn parse_board(body: String) -> Result<board::Board, String> {
+    return envelope::parse(&body).map(|envelope| envelope.board);
+}
+
+// The board for the endpoints that draw or export one: the posted board, with a catalog
+// puzzle's clues as the givens if one is named. With nothing posted it's the catalog puzzle
+// itself.
+async fn posted_board(
+    req: Request<Body>,
+    query: &Query,
+) -> Result<(board::Board, Option<board::Board>), Response<Body>> {
+    let givens = match &query.puzzle {
+        Some(id) => match catalog_board(query, id) {
+            Some(puzzle) => Some(puzzle),
+            None => return Err(not_found(format!("no puzzle with ID {}", id))),
+        },
+        None => None,
+    };
+    let body_string = stringify_body(req).await?;
+    let board = match (body_string.trim().is_empty(), &givens) {
+        (true, Some(givens)) => givens.clone(),
+        _ => parse_board(body_string).map_err(bad_request)?,
+    };
+    return Ok((board, givens));
+}
+
+#[derive(Serialize)]
+struct SharedLinks {
+    fpuzzles: String,
+    sudokupad: String,
+}
+
+#[derive(Serialize)]
+struct ImportedPuzzle {
+    title: Option<String>,
+    author: Option<String>,
+    puzzle: board::Board,
+    state: Option<board::Board>,
+}
+
+fn bad_request(err: String) -> Response<Body> {
+    return Response::builder()
+        .status(StatusCode::BAD_REQUEST)
         .header("Access-Control-Allow-Origin", "*")
-        .body::<Body>("Hello World!".into())
+        .body::<Body>(err.into())
         .unwrap();
-    Ok(res)
 }
 
-async fn stringify_body(req: Request<Body>) -> String {
-    let body = req.into_body();
-    let body_bytes = hyper::body::to_bytes(body).await.unwrap().to_vec();
-    let body_string = String::from_utf8(body_bytes).unwrap();
-    return body_string;
+fn not_found(what: String) -> Response<Body> {
+    return Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header("Access-Control-Allow-Origin", "*")
+        .body::<Body>(what.into())
+        .unwrap();
+}
+
+// Runs CPU bound work off the executor. If it panics, the request gets a 500 rather than taking
+// its connection down with it.
+async fn blocking<T, F>(work: F) -> Result<T, Response<Body>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    return tokio::task::spawn_blocking(work).await.map_err(|err| {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header("Access-Control-Allow-Origin", "*")
+            .body::<Body>(err.to_string().into())
+            .unwrap()
+    });
+}
+
+#[derive(Deserialize)]
+// Either board can be in any of the formats `envelope` reads.
+struct CheckRequest {
+    puzzle: serde_json::Value,
+    board: serde_json::Value,
 }
 
 async fn boogie_board(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let query = match parse_query(req.uri().query()) {
+        Ok(query) => query,
+        Err(err) => return Ok(bad_request(err)),
+    };
     match (req.uri().path(), req.method()) {
         ("/board", &Method::POST) => {
             // let body_string = stringify_body(req).await;
-            println!("Sending NYT Sudoku Board");
-            let starting_board = board::Board::new(crate::consts::nyt_hard_map());
+            let mut starting_board = match &query.puzzle {
+                Some(id) => match catalog_board(&query, id) {
+                    Some(puzzle) => {
+                        println!("Sending Sudoku Board {}", id);
+                        puzzle
+                    }
+                    None => return Ok(not_found(format!("no puzzle with ID {}", id))),
+                },
+                None => {
+                    println!("Sending the daily Sudoku Board");
+                    match daily_puzzle(&query).await {
+                        Ok((_, generated)) => generated.puzzle,
+                        Err(res) => return Ok(res),
+                    }
+                }
+            };
+            starting_board.apply_pencilmark_policy(query.policy);
             // let solved = board::solve(nyt_easy_starting_board);
             let serialized_board = serde_json::to_string(&starting_board).unwrap();
             let res = Response::builder()
@@ -44,28 +383,49 @@ async fn boogie_board(req: Request<Body>) -> Result<Response<Body>, Infallible>
             Ok(res)
         }
         ("/board/solve_all", &Method::GET) => {
-            let body_string = stringify_body(req).await;
-            let board = board::Board::from_str(body_string);
-            let solved_board = board::solve(board);
-            let serialized_solved_board = serde_json::to_string(&solved_board).unwrap();
+            let body_string = match stringify_body(req).await {
+                Ok(body_string) => body_string,
+                Err(res) => return Ok(res),
+            };
+            let board = match parse_board(body_string) {
+                Ok(board) => board,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let solver = board::Solver::init_with_board(board, query.policy);
+            let solver = solver.with_schedule(query.schedule);
+            let budget = query.budget();
+            // Solving is CPU bound, so keep it off the executor's threads.
+            let outcome = match blocking(move || board::solve_within(solver, &budget)).await {
+                Ok(outcome) => outcome,
+                Err(res) => return Ok(res),
+            };
+            let serialized_solved_board = serde_json::to_string(&outcome.result).unwrap();
             let res = Response::builder()
                 .header("Access-Control-Allow-Origin", "*")
                 .header("Access-Control-Allow-Headers", "Content-Type")
+                .header("Access-Control-Expose-Headers", "X-Solve-Stopped")
+                .header("X-Solve-Stopped", outcome.stopped.to_string())
                 .body::<Body>(serialized_solved_board.into())
                 .unwrap();
             Ok(res)
         }
         ("/board/solve_square", &Method::POST) => {
-            let square = match req.uri().query() {
-                None => None,
-                Some(str) => match str.parse::<u8>() {
-                    Ok(box_index) => Some(box_index),
-                    Err(_) => None,
-                },
+            let body_string = match stringify_body(req).await {
+                Ok(body_string) => body_string,
+                Err(res) => return Ok(res),
+            };
+            let board = match parse_board(body_string) {
+                Ok(board) => board,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let solver =
+                board::Solver::init_with_board(board, query.policy).with_schedule(query.schedule);
+            let box_index = query.box_index;
+            let worked_board = match blocking(move || board::work_one_box(solver, box_index)).await
+            {
+                Ok(worked_board) => worked_board,
+                Err(res) => return Ok(res),
             };
-            let body_string = stringify_body(req).await;
-            let solver = board::Solver::init_with_board(board::Board::from_str(body_string));
-            let worked_board = board::work_one_box(solver, square);
             let serialized_worked_board = serde_json::to_string(&worked_board.get_board()).unwrap();
             let res = Response::builder()
                 .header("Access-Control-Allow-Origin", "*")
@@ -73,6 +433,269 @@ async fn boogie_board(req: Request<Body>) -> Result<Response<Body>, Infallible>
                 .unwrap();
             Ok(res)
         }
+        ("/board/check", &Method::POST) => {
+            let body_string = match stringify_body(req).await {
+                Ok(body_string) => body_string,
+                Err(res) => return Ok(res),
+            };
+            let request: CheckRequest = match serde_json::from_str(&body_string) {
+                Ok(request) => request,
+                Err(err) => return Ok(bad_request(err.to_string())),
+            };
+            let puzzle = match parse_board(request.puzzle.to_string()) {
+                Ok(puzzle) => puzzle,
+                Err(err) => return Ok(bad_request(format!("puzzle: {}", err))),
+            };
+            let player = match parse_board(request.board.to_string()) {
+                Ok(player) => player,
+                Err(err) => return Ok(bad_request(format!("board: {}", err))),
+            };
+            let budget = query.budget();
+            let checked = match blocking(move || {
+                check::find_mistakes_within(&puzzle, &player, query.policy, &budget)
+            })
+            .await
+            {
+                Ok(checked) => checked,
+                Err(res) => return Ok(res),
+            };
+            let res = match checked {
+                Ok(mistakes) => Response::builder()
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body::<Body>(serde_json::to_string(&mistakes).unwrap().into())
+                    .unwrap(),
+                Err(err) => Response::builder()
+                    .status(StatusCode::UNPROCESSABLE_ENTITY)
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body::<Body>(err.to_string().into())
+                    .unwrap(),
+            };
+            Ok(res)
+        }
+        ("/board/minimize", &Method::POST) => {
+            let body_string = match stringify_body(req).await {
+                Ok(body_string) => body_string,
+                Err(res) => return Ok(res),
+            };
+            let puzzle = match parse_board(body_string) {
+                Ok(puzzle) => puzzle,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let budget = query.budget();
+            let minimized = match blocking(move || minimal::minimize_within(&puzzle, &budget)).await
+            {
+                Ok(minimized) => minimized,
+                Err(res) => return Ok(res),
+            };
+            let res = match minimized {
+                Ok(minimized) => Response::builder()
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body::<Body>(serde_json::to_string(&minimized).unwrap().into())
+                    .unwrap(),
+                Err(err) => Response::builder()
+                    .status(StatusCode::UNPROCESSABLE_ENTITY)
+                    .header("Access-Control-Allow-Origin", "*")
+                    .body::<Body>(err.to_string().into())
+                    .unwrap(),
+            };
+            Ok(res)
+        }
+        ("/puzzle/daily", &Method::GET) => {
+            let (date, generated) = match daily_puzzle(&query).await {
+                Ok(daily) => daily,
+                Err(res) => return Ok(res),
+            };
+            let mut puzzle = generated.puzzle;
+            puzzle.apply_pencilmark_policy(query.policy);
+            let daily = DailyPuzzle {
+                date: date.to_string(),
+                difficulty: generated.grade.difficulty,
+                hardest: generated.grade.hardest,
+                puzzle,
+            };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&daily).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/board/render.svg", &Method::GET)
+        | ("/board/render.svg", &Method::POST)
+        | ("/board/render.png", &Method::GET)
+        | ("/board/render.png", &Method::POST) => {
+            let png = req.uri().path().ends_with(".png");
+            let (mut board, givens) = match posted_board(req, &query).await {
+                Ok(posted) => posted,
+                Err(res) => return Ok(res),
+            };
+            let mut options = render::RenderOptions::new();
+            options.givens = givens;
+            options.highlighted_cells = query.highlighted_cells.clone();
+            if let Some(cell_size) = query.cell_size {
+                options.cell_size = cell_size;
+            }
+            let hint = query.hint;
+            // Walking to a hint and drawing a large PNG are both CPU bound.
+            let rendered = match blocking(move || {
+                if let Some(hint) = hint {
+                    // The board as it stands before the grader's `hint`th step, with that step
+                    // shown.
+                    let no_step = || format!("no step {} for this board", hint);
+                    let mut grader = grade::Grader::new(&board);
+                    for _ in 1..hint {
+                        grader.step().ok_or_else(no_step)?;
+                    }
+                    let step = grader.next_step().ok_or_else(no_step)?;
+                    if options.givens.is_none() {
+                        options.givens = Some(board.clone());
+                    }
+                    board = grader.to_board();
+                    options = options.with_step(&step);
+                }
+                return Ok(if png {
+                    raster::to_png(&board, &options)
+                } else {
+                    render::to_svg(&board, &options).into_bytes()
+                });
+            })
+            .await
+            {
+                Ok(rendered) => rendered,
+                Err(res) => return Ok(res),
+            };
+            let rendered = match rendered {
+                Ok(rendered) => rendered,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let content_type = if png { "image/png" } else { "image/svg+xml" };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Content-Type", content_type)
+                .body::<Body>(rendered.into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/board/trace.tex", &Method::GET) | ("/board/trace.tex", &Method::POST) => {
+            // A standalone document with the grader's solve path.
+            let (board, _) = match posted_board(req, &query).await {
+                Ok(posted) => posted,
+                Err(res) => return Ok(res),
+            };
+            let budget = query.budget();
+            let outcome = match blocking(move || latex::trace_within(&board, &budget)).await {
+                Ok(outcome) => outcome,
+                Err(res) => return Ok(res),
+            };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .header("Access-Control-Expose-Headers", "X-Solve-Stopped")
+                .header("X-Solve-Stopped", outcome.stopped.to_string())
+                .header("Content-Type", "application/x-tex")
+                .body::<Body>(latex::document(&outcome.result).into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/board/share", &Method::GET) | ("/board/share", &Method::POST) => {
+            // Links that open the board in f-puzzles and SudokuPad. A posted board with a
+            // catalog puzzle named goes as a game in progress on that puzzle.
+            let (board, givens) = match posted_board(req, &query).await {
+                Ok(posted) => posted,
+                Err(res) => return Ok(res),
+            };
+            let title = query
+                .puzzle
+                .as_ref()
+                .and_then(|id| catalog().get(id))
+                .map(|entry| entry.title.clone());
+            let (puzzle, state) = match givens {
+                Some(givens)
+                    if candidates::format_line(&givens) != candidates::format_line(&board) =>
+                {
+                    (givens, Some(board))
+                }
+                _ => (board, None),
+            };
+            let shared = fpuzzles::Shared {
+                title,
+                author: None,
+                puzzle,
+                state,
+            };
+            let links = SharedLinks {
+                fpuzzles: fpuzzles::fpuzzles_url(&shared),
+                sudokupad: fpuzzles::sudokupad_url(&shared),
+            };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&links).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/board/import", &Method::POST) => {
+            // The body is an f-puzzles or SudokuPad link, or the compressed text from one.
+            let body_string = match stringify_body(req).await {
+                Ok(body_string) => body_string,
+                Err(res) => return Ok(res),
+            };
+            let shared = match fpuzzles::decode(&body_string) {
+                Ok(shared) => shared,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let imported = ImportedPuzzle {
+                title: shared.title,
+                author: shared.author,
+                puzzle: shared.puzzle,
+                state: shared.state,
+            };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&imported).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/board/recognize", &Method::POST) => {
+            // The body is a PNG or JPEG of a puzzle. The reply has the board read off it and
+            // how sure each cell is, so the frontend can ask about the doubtful ones.
+            let image = match read_body(req).await {
+                Ok(image) => image,
+                Err(res) => return Ok(res),
+            };
+            // Decoding and matching digits is CPU bound, like solving.
+            let recognized = match blocking(move || recognize::recognize(&image)).await {
+                Ok(recognized) => recognized,
+                Err(res) => return Ok(res),
+            };
+            let recognized = match recognized {
+                Ok(recognized) => recognized,
+                Err(err) => return Ok(bad_request(err)),
+            };
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&recognized).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
+        ("/puzzles", &Method::GET) => {
+            let entries = catalog().filter(&query.filter());
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&entries).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
+        (path, &Method::GET) if path.starts_with("/puzzles/") => {
+            let id = &path["/puzzles/".len()..];
+            let mut puzzle = match catalog_board(&query, id) {
+                Some(puzzle) => puzzle,
+                None => return Ok(not_found(format!("no puzzle with ID {}", id))),
+            };
+            puzzle.apply_pencilmark_policy(query.policy);
+            let res = Response::builder()
+                .header("Access-Control-Allow-Origin", "*")
+                .body::<Body>(serde_json::to_string(&puzzle).unwrap().into())
+                .unwrap();
+            Ok(res)
+        }
         _ => {
             let res = Response::builder()
                 .header("Access-Control-Allow-Origin", "*")
@@ -83,7 +706,8 @@ async fn boogie_board(req: Request<Body>) -> Result<Response<Body>, Infallible>
     }
 }
 
-pub async fn server() {
+pub async fn server(catalog: catalog::Catalog) {
+    CATALOG.set(catalog).unwrap();
     let addr = SocketAddr::from(([127, 0, 0, 1], 4000));
     let hello_service =
         make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(boogie_board)) });
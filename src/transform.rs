@@ -0,0 +1,212 @@
+use crate::board::Board;
+use crate::rng::Rng;
+use std::collections::HashSet;
+
+// Rearrangements that turn a valid puzzle into another valid puzzle with the same solve path:
+// turning and flipping the grid, shuffling rows inside a band or columns inside a stack,
+// shuffling whole bands or stacks, and swapping the digits round.
+//
+// A transform records, for each cell of the result, which cell of the original it copies, and
+// what each digit becomes. Any number of them can be chained with `then`.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    source: [usize; 81],
+    // Indexed by digit; entry 0 is unused.
+    digits: [u8; 10],
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        let mut source = [0; 81];
+        for (idx, from) in source.iter_mut().enumerate() {
+            *from = idx;
+        }
+        return Transform {
+            source,
+            digits: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+        };
+    }
+    // `from(row, col)` gives the original cell the result's (row, col) copies.
+    fn moving_cells(from: impl Fn(usize, usize) -> (usize, usize)) -> Transform {
+        let mut transform = Transform::identity();
+        for (idx, source) in transform.source.iter_mut().enumerate() {
+            let (row, col) = from(idx / 9, idx % 9);
+            *source = 9 * row + col;
+        }
+        return transform;
+    }
+    // A quarter turn clockwise.
+    pub fn rotate() -> Transform {
+        return Transform::moving_cells(|row, col| (8 - col, row));
+    }
+    // Reflection left to right.
+    pub fn mirror() -> Transform {
+        return Transform::moving_cells(|row, col| (row, 8 - col));
+    }
+    // Reflection top to bottom.
+    pub fn flip() -> Transform {
+        return Transform::moving_cells(|row, col| (8 - row, col));
+    }
+    // Reflection in the main diagonal.
+    pub fn transpose() -> Transform {
+        return Transform::moving_cells(|row, col| (col, row));
+    }
+    // Rows and columns are numbered 0-2 within their band or stack.
+    pub fn swap_rows(band: usize, a: usize, b: usize) -> Transform {
+        let (a, b) = (3 * band + a, 3 * band + b);
+        return Transform::moving_cells(move |row, col| (swapped(row, a, b), col));
+    }
+    pub fn swap_cols(stack: usize, a: usize, b: usize) -> Transform {
+        let (a, b) = (3 * stack + a, 3 * stack + b);
+        return Transform::moving_cells(move |row, col| (row, swapped(col, a, b)));
+    }
+    pub fn swap_bands(a: usize, b: usize) -> Transform {
+        return Transform::moving_cells(move |row, col| {
+            (3 * swapped(row / 3, a, b) + row % 3, col)
+        });
+    }
+    pub fn swap_stacks(a: usize, b: usize) -> Transform {
+        return Transform::moving_cells(move |row, col| {
+            (row, 3 * swapped(col / 3, a, b) + col % 3)
+        });
+    }
+    // Digit `d` becomes `relabelling[d - 1]`.
+    pub fn relabel(relabelling: [u8; 9]) -> Result<Transform, String> {
+        let mut seen = [false; 10];
+        let mut transform = Transform::identity();
+        for (i, digit) in relabelling.iter().enumerate() {
+            if *digit < 1 || *digit > 9 || seen[*digit as usize] {
+                return Err(format!("not a relabelling of 1-9: {:?}", relabelling));
+            }
+            seen[*digit as usize] = true;
+            transform.digits[i + 1] = *digit;
+        }
+        return Ok(transform);
+    }
+    // This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        let mut combined = Transform::identity();
+        for idx in 0..81 {
+            combined.source[idx] = self.source[next.source[idx]];
+        }
+        for digit in 1..10 {
+            combined.digits[digit] = next.digits[self.digits[digit] as usize];
+        }
+        return combined;
+    }
+    // A transform picked uniformly from the ones that keep a puzzle valid: the band, stack, row,
+    // column and digit orders are all shuffled, and the grid is transposed half the time.
+    pub fn random(rng: &mut Rng) -> Transform {
+        let mut bands = [0, 1, 2];
+        let mut stacks = [0, 1, 2];
+        rng.shuffle(&mut bands);
+        rng.shuffle(&mut stacks);
+        let mut rows = [0; 9];
+        let mut cols = [0; 9];
+        for band in 0..3 {
+            let mut within = [0, 1, 2];
+            rng.shuffle(&mut within);
+            for i in 0..3 {
+                rows[3 * band + i] = 3 * bands[band] + within[i];
+            }
+            rng.shuffle(&mut within);
+            for i in 0..3 {
+                cols[3 * band + i] = 3 * stacks[band] + within[i];
+            }
+        }
+        let transpose = rng.below(2) == 1;
+        let moved = Transform::moving_cells(|row, col| {
+            if transpose {
+                return (rows[col], cols[row]);
+            }
+            return (rows[row], cols[col]);
+        });
+        let mut relabelling = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        rng.shuffle(&mut relabelling);
+        return moved.then(&Transform::relabel(relabelling).unwrap());
+    }
+    // Pencilmarks move and get relabelled along with the digits.
+    pub fn apply(&self, board: &Board) -> Board {
+        let mut digits = [None; 81];
+        for (idx, digit) in digits.iter_mut().enumerate() {
+            *digit = board
+                .digit(self.source[idx])
+                .map(|digit| self.digits[digit as usize]);
+        }
+        let mut transformed = Board::from_digits(digits);
+        for (idx, digit) in digits.iter().enumerate() {
+            if digit.is_none() {
+                let pencilmarks: HashSet<u8> = board
+                    .pencilmarks(self.source[idx])
+                    .iter()
+                    .map(|digit| self.digits[*digit as usize])
+                    .collect();
+                transformed.set_pencilmarks(idx, pencilmarks);
+            }
+        }
+        return transformed;
+    }
+}
+
+fn swapped(i: usize, a: usize, b: usize) -> usize {
+    if i == a {
+        return b;
+    }
+    if i == b {
+        return a;
+    }
+    return i;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grade;
+    use crate::search;
+
+    fn same_digits(a: &Board, b: &Board) -> bool {
+        return (0..81).all(|idx| a.digit(idx) == b.digit(idx));
+    }
+
+    #[test]
+    fn test_transforms_compose() {
+        let board = crate::catalog::test_board("nyt-easy");
+        let rotate = Transform::rotate();
+        let full_turn = rotate.then(&rotate).then(&rotate).then(&rotate);
+        assert_eq!(full_turn, Transform::identity());
+        assert_eq!(
+            Transform::mirror().then(&Transform::flip()),
+            rotate.then(&rotate)
+        );
+        assert_eq!(Transform::transpose().then(&Transform::mirror()), rotate);
+
+        // cell 0 holds a 4 in the easy board
+        let swapped = Transform::swap_bands(0, 2).then(&Transform::swap_rows(2, 0, 2));
+        assert_eq!(swapped.apply(&board).digit(80 - 8), Some(4));
+        assert_eq!(Transform::swap_stacks(0, 1).apply(&board).digit(3), Some(4));
+        assert_eq!(
+            Transform::swap_cols(0, 0, 1).apply(&board).digit(1),
+            Some(4)
+        );
+
+        let relabel = Transform::relabel([9, 8, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+        assert_eq!(relabel.apply(&board).digit(0), Some(6));
+        assert!(Transform::relabel([1, 1, 3, 4, 5, 6, 7, 8, 9]).is_err());
+    }
+
+    #[test]
+    fn test_random_transforms_keep_the_puzzle() {
+        let board = crate::catalog::test_board("nyt-hard");
+        let graded = grade::grade(&board);
+        let mut rng = Rng::new(17);
+        for _ in 0..5 {
+            let transformed = Transform::random(&mut rng).apply(&board);
+            assert!(!same_digits(&board, &transformed));
+            assert_eq!(search::count_solutions(&transformed, 2), 1);
+            let regraded = grade::grade(&transformed);
+            assert_eq!(regraded.difficulty, graded.difficulty);
+            assert_eq!(regraded.hardest, graded.hardest);
+        }
+    }
+}